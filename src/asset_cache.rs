@@ -1,30 +1,203 @@
 use std::{
-    convert::TryInto,
-    fs::{self, copy, create_dir_all, remove_file},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::{self, copy, create_dir_all, remove_dir_all, remove_file, File},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
 };
 
 use base64::{prelude::BASE64_STANDARD, Engine};
-use log::debug;
-use path_dedot::ParseDot;
+use flate2::write::GzEncoder;
+use log::{debug, log, warn};
 use serde::{
-    de::{Unexpected, Visitor},
+    de::{SeqAccess, Unexpected, Visitor},
     Deserialize, Serialize,
 };
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use crate::{
-    asset_config::AssetConfig,
-    asset_filter::AssetFilterRegistry,
+    asset_config::{
+        AssetConfig, Compression, HashAlgorithm, IntegrityAlgorithm, NamingStrategy, PublishMode,
+    },
+    asset_filter::{filter_options, AssetFilterOption, AssetFilterRegistry},
+    asset_pack::{BuildPlan, PackContext, PackReport, PlannedRebuild, LOCK_FILE_NAME},
     assets::{
-        AssetData, AssetError, AssetErrorType, AssetFilterError, AssetManifest, AssetResult,
-        AssetSource,
+        is_safe_relative_path, AssetData, AssetError, AssetErrorType, AssetFilterError,
+        AssetFiltered, AssetManifest, AssetResult, AssetSource, ChangeDetection, StableAlias,
     },
+    bundle::list_files,
+    dependency_graph::{cycle_in_path, DependencyGraph},
+    observer::PackObserver,
+    runtime_manifest::{append_extension, CONTENT_ENCODINGS},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Content hash made by some hash algorithm. Blake3 hashes are serialized as base64 string (or hexadecimal string with `hex-hash` feature), hashes made by other algorithms are prefixed by algorithm name (`sha256:BASE64`). Both encodings are accepted on deserialization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AssetHash {
-    pub hash: [u8; blake3::OUT_LEN],
+    pub algorithm: HashAlgorithm,
+    pub hash: Vec<u8>,
+}
+
+impl AssetHash {
+    /// Hash bytes by given algorithm.
+    pub fn of_bytes(algorithm: HashAlgorithm, bytes: &[u8]) -> Self {
+        let mut hasher = ContentHasher::new(algorithm);
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+
+    /// Return hash encoded as lowercase hexadecimal digits.
+    pub(crate) fn to_hex(&self) -> String {
+        self.hash
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Decode hash from lowercase or uppercase hexadecimal digits. Return `None` if string is not valid hexadecimal.
+    pub(crate) fn decode_hex(encoded: &str) -> Option<Vec<u8>> {
+        if !encoded.len().is_multiple_of(2) || !encoded.bytes().all(|byte| byte.is_ascii_hexdigit())
+        {
+            return None;
+        }
+        (0..encoded.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&encoded[index..index + 2], 16).ok())
+            .collect()
+    }
+}
+
+/// Incremental hasher producing `AssetHash`.
+pub(crate) enum ContentHasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+}
+
+impl ContentHasher {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => ContentHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            ContentHasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            ContentHasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Hash file content without loading whole file into memory. With `rayon` feature, blake3 hashes large files in parallel (file is memory-mapped).
+    pub(crate) fn update_file(&mut self, file_path: &Path) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "rayon")]
+            ContentHasher::Blake3(hasher) => {
+                hasher.update_mmap_rayon(file_path)?;
+            }
+            #[cfg(not(feature = "rayon"))]
+            ContentHasher::Blake3(hasher) => {
+                hasher.update_reader(File::open(file_path)?)?;
+            }
+            ContentHasher::Sha256(hasher) => {
+                io::copy(&mut File::open(file_path)?, hasher)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finalize(self) -> AssetHash {
+        match self {
+            ContentHasher::Blake3(hasher) => AssetHash {
+                algorithm: HashAlgorithm::Blake3,
+                hash: hasher.finalize().as_bytes().to_vec(),
+            },
+            ContentHasher::Sha256(hasher) => AssetHash {
+                algorithm: HashAlgorithm::Sha256,
+                hash: hasher.finalize().to_vec(),
+            },
+        }
+    }
+}
+
+/// Format hash the same way as it is serialized.
+impl fmt::Display for AssetHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "hex-hash")]
+        let encoded = self.to_hex();
+        #[cfg(not(feature = "hex-hash"))]
+        let encoded = BASE64_STANDARD.encode(&self.hash);
+        match self.algorithm {
+            HashAlgorithm::Blake3 => write!(f, "{}", encoded),
+            algorithm => write!(f, "{}:{}", algorithm.name(), encoded),
+        }
+    }
+}
+
+/// Error of parsing `AssetHash` from string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseAssetHashError {
+    /// Algorithm prefix is not known hash algorithm name.
+    UnknownAlgorithm(String),
+    /// Hash is neither valid base64 nor valid hexadecimal string.
+    InvalidEncoding(String),
+    /// Decoded hash length does not match algorithm output length.
+    InvalidLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ParseAssetHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseAssetHashError::UnknownAlgorithm(name) => {
+                write!(f, "unknown hash algorithm {}", name)
+            }
+            ParseAssetHashError::InvalidEncoding(encoded) => {
+                write!(f, "hash {} is not valid base64 or hexadecimal", encoded)
+            }
+            ParseAssetHashError::InvalidLength { expected, actual } => write!(
+                f,
+                "hash has length {} bytes, expected {} bytes",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseAssetHashError {}
+
+/// Parse hash formatted by `Display` (base64 or hexadecimal, optionally prefixed by algorithm name).
+impl FromStr for AssetHash {
+    type Err = ParseAssetHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, encoded) = match s.split_once(':') {
+            Some((name, encoded)) => (
+                HashAlgorithm::from_name(name)
+                    .ok_or_else(|| ParseAssetHashError::UnknownAlgorithm(name.to_string()))?,
+                encoded,
+            ),
+            None => (HashAlgorithm::Blake3, s),
+        };
+        // Hexadecimal string is twice as long as hash, base64 string of the same hash is always shorter.
+        let hash = if encoded.len() == algorithm.output_len() * 2 {
+            AssetHash::decode_hex(encoded)
+        } else {
+            BASE64_STANDARD.decode(encoded).ok()
+        }
+        .ok_or_else(|| ParseAssetHashError::InvalidEncoding(encoded.to_string()))?;
+        if hash.len() != algorithm.output_len() {
+            return Err(ParseAssetHashError::InvalidLength {
+                expected: algorithm.output_len(),
+                actual: hash.len(),
+            });
+        }
+        Ok(AssetHash { algorithm, hash })
+    }
 }
 
 impl Serialize for AssetHash {
@@ -32,7 +205,7 @@ impl Serialize for AssetHash {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&BASE64_STANDARD.encode(self.hash))
+        serializer.collect_str(self)
     }
 }
 
@@ -42,20 +215,36 @@ impl<'de> Visitor<'de> for HashVisitor {
     type Value = AssetHash;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("base64-encoded blake3 hash")
+        formatter.write_str("base64 or hexadecimal hash (optionally prefixed by algorithm name) or array of blake3 hash bytes")
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        let bytes = BASE64_STANDARD
-            .decode(v)
-            .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))?;
+        v.parse().map_err(|err| match err {
+            ParseAssetHashError::InvalidLength { actual, .. } => E::invalid_length(actual, &self),
+            _ => E::invalid_value(Unexpected::Str(v), &self),
+        })
+    }
+
+    /// Legacy cache manifests store blake3 hash as array of bytes.
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut hash = vec![0; blake3::OUT_LEN];
+        for (index, byte) in hash.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(index, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(serde::de::Error::invalid_length(blake3::OUT_LEN + 1, &self));
+        }
         Ok(AssetHash {
-            hash: bytes
-                .try_into()
-                .map_err(|_| E::invalid_length(v.len(), &self))?,
+            algorithm: HashAlgorithm::Blake3,
+            hash,
         })
     }
 }
@@ -65,7 +254,7 @@ impl<'de> Deserialize<'de> for AssetHash {
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_str(HashVisitor)
+        deserializer.deserialize_any(HashVisitor)
     }
 }
 
@@ -75,20 +264,53 @@ pub struct AssetCacheEntry {
     pub data: AssetData,
     pub path: PathBuf,
     pub file_hash: Option<AssetHash>,
+    /// Source file modification time for assets loaded from source files.
+    #[serde(default)]
+    pub source_modified: Option<SystemTime>,
+    /// Source file size in bytes for assets loaded from source files.
+    #[serde(default)]
+    pub source_size: Option<u64>,
+    /// Hash of filter name and options for filtered assets.
+    #[serde(default)]
+    pub options_fingerprint: Option<AssetHash>,
+    /// Combined hash of input cache entries (paths, hashes and fingerprints) for filtered assets.
+    #[serde(default)]
+    pub inputs_fingerprint: Option<AssetHash>,
+    /// Path of non-hashed alias in target directory, if it was created.
+    #[serde(default)]
+    pub alias_path: Option<PathBuf>,
+    /// Whether asset is listed in public assets of manifest (updated every time asset is processed).
+    #[serde(default)]
+    pub is_public: bool,
+    /// Time when asset file was built (taken from pack context clock).
+    #[serde(default)]
+    pub built_at: Option<SystemTime>,
+    /// Full hash of internal file content, stored with `ContentHash` naming strategy (file name may contain truncated hash).
+    #[serde(default)]
+    pub output_hash: Option<AssetHash>,
+    /// Paths of files in asset directory (relative to it) for assets built from glob pattern.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// Subresource Integrity string of public asset file (including banner), if `integrity` is set in config.
+    #[serde(default)]
+    pub integrity: Option<String>,
+    /// Compressions of precompressed variants written for public asset file, see `precompress` in config.
+    #[serde(default)]
+    pub precompressed: Vec<Compression>,
 }
 
 impl AssetCacheEntry {
     pub fn create<E>(
         name: String,
-        config: &AssetConfig,
-        manifest: &AssetManifest,
         cache_manifest: &mut AssetCacheManifestV1,
-        filter_registry: &AssetFilterRegistry<E>,
+        context: &mut PackContext<E>,
     ) -> AssetResult<AssetCacheEntry, E>
     where
         E: AssetFilterError,
     {
-        let data = manifest
+        let config = context.config;
+        let data = context
+            .manifest
             .assets
             .get(&name)
             .ok_or_else(|| {
@@ -98,61 +320,236 @@ impl AssetCacheEntry {
 
         let uuid = Uuid::new_v4();
 
-        let output_path: PathBuf = match &data.output_base_path {
-            Some(base_path) => base_path
-                .clone()
-                .join(name.clone() + "-" + &uuid.to_string())
-                .with_extension(data.extension.clone()),
-            None => Path::new(&(name.clone() + "-" + &uuid.to_string()))
-                .with_extension(data.extension.clone()),
-        };
-        if output_path.has_root() || output_path.parse_dot()?.starts_with("..") {
+        let output_path = data.output_path(&name, &uuid.to_string(), config);
+        if !is_safe_relative_path(&output_path) {
             return Err(AssetError::new(AssetErrorType::AssetPathError(output_path)));
         }
         let output_full_path = config.internal_directory_path.join(output_path.clone());
+        let log_level = data.log_level();
+        log!(
+            log_level,
+            "Building asset {} to {:?}",
+            name,
+            output_full_path
+        );
 
+        let mut source_modified = None;
+        let mut source_size = None;
+        let mut options_fingerprint = None;
+        let mut inputs_fingerprint = None;
+        let mut files = Vec::new();
         let file_hash = match &data.source {
             AssetSource::File(file_path) => {
-                let source_full_path = config.source_directory_path.join(file_path);
+                let source_full_path = context.source_file_path(file_path)?;
 
                 debug!("Copying {:?} to {:?}", source_full_path, output_full_path);
                 if let Some(output_full_path_parent) = output_full_path.parent() {
                     create_dir_all(output_full_path_parent)?;
                 }
                 copy(&source_full_path, &output_full_path)?;
+                if config.preserve_metadata {
+                    copy_metadata(&source_full_path, &output_full_path)?;
+                }
+                let source_metadata = fs::metadata(&source_full_path)?;
+                source_modified = Some(source_metadata.modified()?);
+                source_size = Some(source_metadata.len());
 
-                let file_bytes = fs::read(&output_full_path)?;
-                let file_hash = blake3::hash(file_bytes.as_slice());
-                Some(*file_hash.as_bytes())
+                if data.change_detection == ChangeDetection::Mtime {
+                    None
+                } else {
+                    let file_hash = content_hash(config.hash_algorithm, &output_full_path)?;
+                    log!(
+                        log_level,
+                        "Asset {} source {:?} has hash {}",
+                        name,
+                        source_full_path,
+                        file_hash.to_hex()
+                    );
+                    Some(file_hash)
+                }
+            }
+            AssetSource::Inline(bytes) => {
+                debug!("Writing inline content to {:?}", output_full_path);
+                if let Some(output_full_path_parent) = output_full_path.parent() {
+                    create_dir_all(output_full_path_parent)?;
+                }
+                fs::write(&output_full_path, bytes)?;
+                let file_hash = AssetHash::of_bytes(config.hash_algorithm, bytes);
+                log!(
+                    log_level,
+                    "Asset {} inline content has hash {}",
+                    name,
+                    file_hash.to_hex()
+                );
+                Some(file_hash)
+            }
+            AssetSource::Glob(pattern) => {
+                files = context.glob_source_files(&name, pattern)?;
+                copy_files(
+                    &context.source_root,
+                    &output_full_path,
+                    &files,
+                    config.preserve_metadata,
+                )?;
+                let files_hash = hash_files(config.hash_algorithm, &output_full_path, &files)?;
+                log!(
+                    log_level,
+                    "Asset {} matches {} files with combined hash {}",
+                    name,
+                    files.len(),
+                    files_hash.to_hex()
+                );
+                Some(files_hash)
+            }
+            AssetSource::Directory(directory_path) => {
+                let source_full_path = context.source_directory_path(directory_path)?;
+                files = directory_files(&source_full_path)?;
+                copy_files(
+                    &source_full_path,
+                    &output_full_path,
+                    &files,
+                    config.preserve_metadata,
+                )?;
+                let files_hash = hash_files(config.hash_algorithm, &output_full_path, &files)?;
+                log!(
+                    log_level,
+                    "Asset {} directory {:?} has {} files with combined hash {}",
+                    name,
+                    source_full_path,
+                    files.len(),
+                    files_hash.to_hex()
+                );
+                Some(files_hash)
             }
             AssetSource::Filtered(filtered) => {
-                let mut input_full_paths: Vec<PathBuf> =
+                let mut input_entries: Vec<AssetCacheEntry> =
                     Vec::with_capacity(filtered.input_names.len());
                 for input_name in &filtered.input_names {
-                    input_full_paths.push(
-                        config.internal_directory_path.join(
-                            cache_manifest
-                                .process(input_name.clone(), config, manifest, filter_registry)?
-                                .0
-                                .path,
-                        ),
-                    );
+                    input_entries.push(cache_manifest.process(input_name.clone(), context)?.0);
+                }
+                let input_full_paths: Vec<PathBuf> = input_entries
+                    .iter()
+                    .flat_map(|input_entry| input_entry.input_full_paths(config))
+                    .collect();
+
+                if input_full_paths.is_empty()
+                    && context
+                        .filter_registry
+                        .accepts_empty_inputs(&filtered.filter_name)
+                        == Some(false)
+                {
+                    return Err(AssetError::new(AssetErrorType::NoFilterInputs(name)));
                 }
 
-                filter_registry
-                    .process_asset_file(
-                        filtered.filter_name.clone(),
-                        &input_full_paths,
-                        &output_full_path,
-                        &filtered.options,
-                    )
-                    .ok_or_else(|| {
-                        AssetError::new(AssetErrorType::AssetFilterNotFoundError(
-                            filtered.filter_name.clone(),
-                        ))
-                    })??;
+                log!(
+                    log_level,
+                    "Asset {} is built by filter {} from {:?} with options {:?}",
+                    name,
+                    filtered.filter_name,
+                    input_full_paths,
+                    filtered.options
+                );
 
-                None
+                let options = filter_options(&filtered.options, config)?;
+                match context.prepared_outputs.remove(&name) {
+                    Some(prepared_full_path) => {
+                        debug!(
+                            "Moving prepared filter output {:?} to {:?}",
+                            prepared_full_path, output_full_path
+                        );
+                        if let Some(output_full_path_parent) = output_full_path.parent() {
+                            create_dir_all(output_full_path_parent)?;
+                        }
+                        fs::rename(&prepared_full_path, &output_full_path)?;
+                    }
+                    None if context.async_filters => {
+                        return Err(AssetError::new(AssetErrorType::FilterOutputNotPrepared(
+                            name,
+                        )));
+                    }
+                    None => {
+                        context
+                            .filter_registry
+                            .process_asset_file(
+                                filtered.filter_name.clone(),
+                                &input_full_paths,
+                                &output_full_path,
+                                &options,
+                            )
+                            .ok_or_else(|| {
+                                AssetError::new(AssetErrorType::AssetFilterNotFoundError(
+                                    filtered.filter_name.clone(),
+                                ))
+                            })??;
+                    }
+                }
+
+                if let Some(Err(err)) = context.filter_registry.validate_output(
+                    &filtered.filter_name,
+                    &output_full_path,
+                    &options,
+                ) {
+                    debug!("Removing invalid filter output {:?}", output_full_path);
+                    if output_full_path.exists() {
+                        remove_file(&output_full_path)?;
+                    }
+                    return Err(err);
+                }
+
+                options_fingerprint = Some(fingerprint_options(&filtered.filter_name, &options)?);
+                inputs_fingerprint = Some(fingerprint_inputs(&input_entries));
+
+                let file_hash = content_hash(config.hash_algorithm, &output_full_path)?;
+                log!(
+                    log_level,
+                    "Asset {} output has hash {}",
+                    name,
+                    file_hash.to_hex()
+                );
+                Some(file_hash)
+            }
+        };
+
+        let integrity = match (config.integrity, &data.source) {
+            (Some(_), AssetSource::Glob(_) | AssetSource::Directory(_)) | (None, _) => None,
+            (Some(algorithm), AssetSource::Inline(bytes)) => {
+                Some(integrity_with_banner(algorithm, &data, bytes.as_slice())?)
+            }
+            (Some(algorithm), _) => Some(integrity_with_banner(
+                algorithm,
+                &data,
+                File::open(&output_full_path)?,
+            )?),
+        };
+
+        let (output_path, output_hash) = match config.naming {
+            NamingStrategy::Uuid => (output_path, None),
+            NamingStrategy::ContentHash => {
+                // Only version suffix is changed and encoded hash has no path separators, so path stays safe.
+                let output_hash = match (&data.source, &file_hash) {
+                    (AssetSource::Glob(_) | AssetSource::Directory(_), Some(files_hash)) => {
+                        files_hash.clone()
+                    }
+                    (AssetSource::Filtered(_) | AssetSource::Inline(_), Some(file_hash)) => {
+                        file_hash.clone()
+                    }
+                    _ => content_hash(config.hash_algorithm, &output_full_path)?,
+                };
+                let content_path = data.content_path(&name, &output_hash.hash, config);
+                if let Err(err) = check_hash_collision(&name, &content_path, &output_hash, config) {
+                    if output_full_path.is_dir() {
+                        remove_dir_all(&output_full_path)?;
+                    } else {
+                        remove_file(&output_full_path)?;
+                    }
+                    return Err(err);
+                }
+                let content_full_path = config.internal_directory_path.join(&content_path);
+                if let Some(content_full_path_parent) = content_full_path.parent() {
+                    create_dir_all(content_full_path_parent)?;
+                }
+                replace_path(&output_full_path, &content_full_path)?;
+                (content_path, Some(output_hash))
             }
         };
 
@@ -160,21 +557,32 @@ impl AssetCacheEntry {
             name,
             data,
             path: output_path,
-            file_hash: file_hash.map(|hash| AssetHash { hash }),
+            file_hash,
+            source_modified,
+            source_size,
+            options_fingerprint,
+            inputs_fingerprint,
+            alias_path: None,
+            is_public: false,
+            built_at: Some(context.clock.now()),
+            output_hash,
+            files,
+            integrity,
+            precompressed: Vec::new(),
         })
     }
 
     pub fn update<E>(
         &self,
-        config: &AssetConfig,
-        manifest: &AssetManifest,
         cache_manifest: &mut AssetCacheManifestV1,
-        filter_registry: &AssetFilterRegistry<E>,
+        context: &mut PackContext<E>,
     ) -> AssetResult<Option<AssetCacheEntry>, E>
     where
         E: AssetFilterError,
     {
-        let new_data = manifest
+        let config = context.config;
+        let new_data = context
+            .manifest
             .assets
             .get(&self.name)
             .ok_or_else(|| {
@@ -186,150 +594,1851 @@ impl AssetCacheEntry {
 
         let full_path = config.internal_directory_path.join(self.path.clone());
 
-        let need_update: bool = if (new_data != self.data) || !full_path.exists() {
-            true
-        } else {
-            match self.data.source.clone() {
-                AssetSource::File(path) => {
-                    let full_path = config.source_directory_path.join(path);
-                    let file_bytes = fs::read(full_path)?;
-                    let file_hash = blake3::hash(file_bytes.as_slice());
+        // Asset rebuilt earlier in this run (for example, by `process_async` before resolving its dependents) is not rebuilt again.
+        if context.plan.is_none() && context.rebuilt.contains(&self.name) && full_path.exists() {
+            debug!("Asset {} was already rebuilt in this run", self.name);
+            return Ok(None);
+        }
 
-                    if let Some(self_file_hash_bytes) = &self.file_hash {
-                        file_hash.as_bytes() != &self_file_hash_bytes.hash
-                    } else {
-                        true
+        let rebuild_reason: Option<&str> = match context.plan {
+            // Decisions were made by plan, only files removed after planning are checked. Inputs are still processed to resolve them in order.
+            Some(plan) => {
+                let mut has_updated_inputs = false;
+                let mut input_entries: Vec<AssetCacheEntry> = Vec::new();
+                if let AssetSource::Filtered(filtered) = &new_data.source {
+                    for input_name in &filtered.input_names {
+                        let (input_entry, changed) =
+                            cache_manifest.process(input_name.clone(), context)?;
+                        has_updated_inputs |= changed;
+                        input_entries.push(input_entry);
                     }
                 }
-                AssetSource::Filtered(filtered) => {
-                    let mut has_updated_inputs = false;
-                    for input_name in filtered.input_names {
-                        let (_, changed) = cache_manifest.process(
-                            input_name,
-                            config,
-                            manifest,
-                            filter_registry,
-                        )?;
-                        if changed {
-                            has_updated_inputs = true;
-                            break;
-                        }
+                if context.rebuilt.contains(&self.name) {
+                    None
+                } else if !full_path.exists() {
+                    Some("internal file is missing")
+                } else {
+                    match (plan.reason(&self.name), &new_data.source) {
+                        // Plan can not know content of rebuilt inputs, so it is checked now.
+                        (Some(INPUTS_REBUILT_REASON), AssetSource::Filtered(filtered)) => self
+                            .inputs_rebuild_reason(
+                                filtered,
+                                &input_entries,
+                                has_updated_inputs,
+                                context.config,
+                            )?,
+                        (reason, _) => reason,
                     }
-
-                    has_updated_inputs
                 }
             }
+            None => match self.check_stale(&new_data, context)? {
+                StaleCheck::Rebuild(reason) => Some(reason),
+                StaleCheck::UpToDate => None,
+                StaleCheck::CheckInputs(filtered) => {
+                    let mut has_updated_inputs = false;
+                    let mut input_entries: Vec<AssetCacheEntry> =
+                        Vec::with_capacity(filtered.input_names.len());
+                    for input_name in &filtered.input_names {
+                        let (input_entry, changed) =
+                            cache_manifest.process(input_name.clone(), context)?;
+                        has_updated_inputs |= changed;
+                        input_entries.push(input_entry);
+                    }
+                    self.inputs_rebuild_reason(
+                        &filtered,
+                        &input_entries,
+                        has_updated_inputs,
+                        context.config,
+                    )?
+                }
+            },
         };
 
-        if need_update {
-            if full_path.exists() {
-                remove_file(full_path)?;
-            }
+        // Callback can not prevent rebuilding asset with missing internal file. Plan already includes callback decisions.
+        let rebuild_override = context
+            .should_rebuild
+            .filter(|_| context.plan.is_none())
+            .and_then(|should_rebuild| should_rebuild(self, &new_data));
+        let rebuild_reason = match rebuild_override {
+            Some(true) => Some("rebuild requested by callback"),
+            Some(false) if full_path.exists() => None,
+            _ => rebuild_reason,
+        };
 
-            let target_full_path = config.target_directory_path.join(self.path.clone());
-            if target_full_path.exists() {
-                remove_file(target_full_path)?;
+        if let Some(rebuild_reason) = rebuild_reason {
+            log!(
+                new_data.log_level(),
+                "Rebuilding asset {}: {}",
+                self.name,
+                rebuild_reason
+            );
+            if cache_manifest.path_references(&self.path) > 1 {
+                debug!("Keeping {:?} used by other assets", self.path);
+            } else {
+                self.remove_files(config)?;
             }
 
-            return AssetCacheEntry::create(
-                self.name.clone(),
-                config,
-                manifest,
-                cache_manifest,
-                filter_registry,
-            )
-            .map(Option::Some);
+            return AssetCacheEntry::create(self.name.clone(), cache_manifest, context)
+                .map(Option::Some);
         }
 
+        log!(
+            new_data.log_level(),
+            "Asset {} is up to date at {:?}",
+            self.name,
+            full_path
+        );
         Ok(None)
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum AssetCacheManifestVersioned {
-    V1(AssetCacheManifestV1),
-}
+    /// Check if cached asset should be rebuilt without resolving its inputs. Filtered assets with unchanged definition should be checked by `inputs_rebuild_reason` after resolving inputs.
+    fn check_stale<E>(
+        &self,
+        new_data: &AssetData,
+        context: &PackContext<E>,
+    ) -> AssetResult<StaleCheck, E>
+    where
+        E: AssetFilterError,
+    {
+        let full_path = context
+            .config
+            .internal_directory_path
+            .join(self.path.clone());
 
-impl Default for AssetCacheManifestVersioned {
-    fn default() -> Self {
-        AssetCacheManifestVersioned::V1(AssetCacheManifestV1::default())
+        let rebuild_reason = if new_data.change_detection == ChangeDetection::Always {
+            Some("change detection is Always")
+        } else if new_data.change_detection == ChangeDetection::Never {
+            (!full_path.exists()).then_some("internal file is missing")
+        } else if new_data != &self.data {
+            Some("asset definition changed")
+        } else if !full_path.exists() {
+            Some("internal file is missing")
+        } else if !matches!(self.data.source, AssetSource::Filtered(_))
+            && self
+                .file_hash
+                .as_ref()
+                .is_some_and(|file_hash| file_hash.algorithm != context.config.hash_algorithm)
+        {
+            // Stored hash can not be compared with hash made by configured algorithm.
+            Some("hash algorithm changed")
+        } else {
+            match &self.data.source {
+                AssetSource::File(path) => {
+                    let full_path = context.source_file_path(path)?;
+
+                    if self.data.change_detection == ChangeDetection::Mtime {
+                        let modified = fs::metadata(full_path)?.modified()?;
+                        (self.source_modified != Some(modified))
+                            .then_some("source file modification time changed")
+                    } else if self.file_hash.is_some()
+                        && !context.config.always_hash_sources
+                        && self.source_metadata_matches(&full_path)?
+                    {
+                        // Size and modification time are unchanged, so file is not read.
+                        None
+                    } else {
+                        let file_hash = content_hash(context.config.hash_algorithm, &full_path)?;
+                        log!(
+                            new_data.log_level(),
+                            "Asset {} source {:?} has hash {}",
+                            self.name,
+                            full_path,
+                            file_hash.to_hex()
+                        );
+
+                        if let Some(self_file_hash) = &self.file_hash {
+                            (&file_hash != self_file_hash).then_some("source file hash changed")
+                        } else {
+                            Some("source file hash is not stored")
+                        }
+                    }
+                }
+                AssetSource::Inline(bytes) => {
+                    let file_hash = AssetHash::of_bytes(context.config.hash_algorithm, bytes);
+                    (self.file_hash.as_ref() != Some(&file_hash))
+                        .then_some("inline content hash changed")
+                }
+                AssetSource::Glob(pattern) => {
+                    let files = context.glob_source_files(&self.name, pattern)?;
+                    let files_hash =
+                        hash_files(context.config.hash_algorithm, &context.source_root, &files)?;
+                    (self.files != files || self.file_hash.as_ref() != Some(&files_hash))
+                        .then_some("matched source files changed")
+                }
+                AssetSource::Directory(directory_path) => {
+                    let source_full_path = context.source_directory_path(directory_path)?;
+                    let files = directory_files(&source_full_path)?;
+                    let files_hash =
+                        hash_files(context.config.hash_algorithm, &source_full_path, &files)?;
+                    (self.files != files || self.file_hash.as_ref() != Some(&files_hash))
+                        .then_some("source directory files changed")
+                }
+                AssetSource::Filtered(filtered) => {
+                    return Ok(StaleCheck::CheckInputs(filtered.clone()));
+                }
+            }
+        };
+
+        Ok(match rebuild_reason {
+            Some(reason) => StaleCheck::Rebuild(reason),
+            None => StaleCheck::UpToDate,
+        })
     }
-}
 
-/// Asset cache manifest. It contains current file paths, data to check if assets are modified, etc.
-#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
-pub struct AssetCacheManifestV1 {
-    pub map: std::collections::HashMap<String, AssetCacheEntry>,
-}
+    /// Return `true` if source file has stored size and modification time.
+    fn source_metadata_matches(&self, source_full_path: &Path) -> io::Result<bool> {
+        let metadata = fs::metadata(source_full_path)?;
+        Ok(self.source_size == Some(metadata.len())
+            && self.source_modified == Some(metadata.modified()?))
+    }
 
-impl AssetCacheManifestV1 {
-    pub fn process<E>(
-        &mut self,
-        name: String,
+    /// Check if cached filtered asset should be rebuilt, given its resolved input entries.
+    pub(crate) fn inputs_rebuild_reason<E>(
+        &self,
+        filtered: &AssetFiltered,
+        input_entries: &[AssetCacheEntry],
+        has_updated_inputs: bool,
         config: &AssetConfig,
-        manifest: &AssetManifest,
-        filter_registry: &AssetFilterRegistry<E>,
-    ) -> AssetResult<(AssetCacheEntry, bool), E>
+    ) -> AssetResult<Option<&'static str>, E>
     where
         E: AssetFilterError,
     {
-        let cache_entry_optional = self.map.get(&name).cloned();
-        match cache_entry_optional {
-            Some(cache_entry) => {
-                if let Some(cache_entry_new) =
-                    cache_entry.update(config, manifest, self, filter_registry)?
-                {
-                    self.map.insert(name, cache_entry_new.clone());
-                    Ok((cache_entry_new, true))
+        // Fingerprints catch input changes not visible through `changed` flags, for example inputs rebuilt by previous failed run.
+        // Rebuilt inputs with stored content hashes and byte-identical content do not cause rebuild.
+        let has_content_hashes = input_entries
+            .iter()
+            .all(|input_entry| input_entry.file_hash.is_some());
+        Ok(
+            if self.options_fingerprint != Some(fingerprint_filter_options(filtered, config)?) {
+                Some("filter options changed")
+            } else if self.inputs_fingerprint != Some(fingerprint_inputs(input_entries)) {
+                if has_updated_inputs {
+                    Some(INPUTS_REBUILT_REASON)
                 } else {
-                    Ok((cache_entry, false))
+                    Some("input assets changed")
                 }
+            } else if has_updated_inputs && !has_content_hashes {
+                Some(INPUTS_REBUILT_REASON)
+            } else {
+                None
+            },
+        )
+    }
+
+    /// Return full paths of internal files passed to filter when asset is used as filter input: all asset files for glob and directory assets, asset file for other assets.
+    pub fn input_full_paths(&self, config: &AssetConfig) -> Vec<PathBuf> {
+        let internal_full_path = config.internal_directory_path.join(&self.path);
+        match self.data.source {
+            AssetSource::Glob(_) | AssetSource::Directory(_) => self
+                .files
+                .iter()
+                .map(|file| internal_full_path.join(file))
+                .collect(),
+            _ => vec![internal_full_path],
+        }
+    }
+
+    /// Remove asset files from target directories (including alias) and internal directory. Target files are removed first, as they can be links to internal file.
+    pub fn remove_files(&self, config: &AssetConfig) -> io::Result<()> {
+        for target_directory_path in config.target_directory_paths() {
+            let target_full_path = target_directory_path.join(&self.path);
+            // Links are not followed, so link to removed internal file is removed too.
+            match target_full_path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => remove_dir_all(target_full_path)?,
+                Ok(_) => remove_file(target_full_path)?,
+                Err(_) => {}
             }
-            None => {
-                let result =
-                    AssetCacheEntry::create(name.clone(), config, manifest, self, filter_registry);
-                if let Ok(cache_entry) = &result {
-                    self.map.insert(name, cache_entry.clone());
+
+            if let Some(alias_path) = &self.alias_path {
+                let alias_full_path = target_directory_path.join(alias_path);
+                if alias_full_path.symlink_metadata().is_ok() {
+                    remove_file(alias_full_path)?;
                 }
-                result.map(|cache_entry| (cache_entry, true))
             }
         }
+
+        let internal_full_path = config.internal_directory_path.join(&self.path);
+        if internal_full_path.is_dir() {
+            remove_dir_all(internal_full_path)?;
+        } else if internal_full_path.exists() {
+            remove_file(internal_full_path)?;
+        }
+
+        Ok(())
     }
 
-    pub fn process_public_assets<E>(
-        &mut self,
-        config: &AssetConfig,
-        manifest: &AssetManifest,
-        filter_registry: &AssetFilterRegistry<E>,
-    ) -> AssetResult<(), E>
-    where
-        E: AssetFilterError,
+    /// Rename entry files in internal and target directories to new path (relative to these directories). Internal file is renamed last, so partially renamed entry can be renamed again. Symbolic links to internal file are created again for renamed internal file.
+    fn rename_files(&self, new_path: &Path, config: &AssetConfig) -> io::Result<()> {
+        let mut renamed_symlinks: Vec<PathBuf> = Vec::new();
+        for target_directory_path in config.target_directory_paths() {
+            let target_full_path = target_directory_path.join(&self.path);
+            let is_symlink = match target_full_path.symlink_metadata() {
+                Ok(metadata) => metadata.file_type().is_symlink(),
+                Err(_) => continue,
+            };
+            let new_target_full_path = target_directory_path.join(new_path);
+            debug!(
+                "Renaming {:?} to {:?}",
+                target_full_path, new_target_full_path
+            );
+            replace_path(&target_full_path, &new_target_full_path)?;
+            if is_symlink {
+                renamed_symlinks.push(new_target_full_path.clone());
+            }
+
+            if let (Some(alias_path), Some(StableAlias::Symlink)) =
+                (&self.alias_path, self.data.stable_alias)
+            {
+                create_alias(
+                    &new_target_full_path,
+                    &target_directory_path.join(alias_path),
+                    StableAlias::Symlink,
+                )?;
+            }
+        }
+
+        let internal_full_path = config.internal_directory_path.join(&self.path);
+        let new_internal_full_path = config.internal_directory_path.join(new_path);
+        debug!(
+            "Renaming {:?} to {:?}",
+            internal_full_path, new_internal_full_path
+        );
+        replace_path(&internal_full_path, &new_internal_full_path)?;
+
+        for new_target_full_path in renamed_symlinks {
+            remove_file(&new_target_full_path)?;
+            publish_file(
+                &new_internal_full_path,
+                &new_target_full_path,
+                PublishMode::Symlink,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Rename file or directory, replacing existing one. Existing directory (for example, identical output of directory asset with content hash name) is removed first, as it can not be replaced by rename.
+fn replace_path(path: &Path, new_path: &Path) -> io::Result<()> {
+    if path.is_dir() && new_path.is_dir() {
+        remove_dir_all(new_path)?;
+    }
+    fs::rename(path, new_path)
+}
+
+/// Remove files in directory (recursively) with relative paths not accepted by `is_referenced`, append their full paths.
+fn prune_directory<F>(
+    directory_path: &Path,
+    is_referenced: F,
+    pruned: &mut Vec<PathBuf>,
+) -> io::Result<()>
+where
+    F: Fn(&Path) -> bool,
+{
+    if !directory_path.is_dir() {
+        return Ok(());
+    }
+    prune_subdirectory(directory_path, Path::new(""), &is_referenced, pruned)?;
+    Ok(())
+}
+
+/// Prune subdirectory (relative to root directory), remove its subdirectories left empty. Symbolic links are removed as files and not followed, so files outside root directory are kept. Return `true` if subdirectory is empty after pruning.
+fn prune_subdirectory<F>(
+    root_path: &Path,
+    prefix: &Path,
+    is_referenced: &F,
+    pruned: &mut Vec<PathBuf>,
+) -> io::Result<bool>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut is_empty = true;
+    for entry in fs::read_dir(root_path.join(prefix))? {
+        let entry = entry?;
+        let relative_path = prefix.join(entry.file_name());
+        let full_path = root_path.join(&relative_path);
+        if is_referenced(&relative_path) {
+            is_empty = false;
+        } else if entry.file_type()?.is_dir() {
+            if prune_subdirectory(root_path, &relative_path, is_referenced, pruned)? {
+                debug!("Removing empty directory {:?}", full_path);
+                fs::remove_dir(&full_path)?;
+            } else {
+                is_empty = false;
+            }
+        } else {
+            debug!("Pruning {:?}", full_path);
+            remove_file(&full_path)?;
+            pruned.push(full_path);
+        }
+    }
+    Ok(is_empty)
+}
+
+/// Hash paths and contents of files in directory (paths relative to it), so adding, removing or renaming file changes hash.
+fn hash_files(
+    algorithm: HashAlgorithm,
+    directory_path: &Path,
+    files: &[PathBuf],
+) -> io::Result<AssetHash> {
+    let mut hasher = ContentHasher::new(algorithm);
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&content_hash(algorithm, &directory_path.join(file))?.hash);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Check that all public asset names are defined in manifest before processing any of them.
+pub(crate) fn check_public_asset_names<E>(
+    names: &[String],
+    context: &PackContext<E>,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    if let Some(name) = names
+        .iter()
+        .find(|name| !context.manifest.assets.contains_key(*name))
     {
-        for asset_name in &manifest.public_assets {
-            let (cache_entry, _) =
-                self.process(asset_name.clone(), config, manifest, filter_registry)?;
+        return Err(AssetError::new(AssetErrorType::PublicAssetNotDefined(
+            name.clone(),
+        )));
+    }
+    match names
+        .iter()
+        .find(|name| matches!(context.manifest.assets[*name].source, AssetSource::Glob(_)))
+    {
+        Some(name) => Err(AssetError::new(AssetErrorType::PublicGlobAsset(
+            name.clone(),
+        ))),
+        None => Ok(()),
+    }
+}
 
-            let source_full_path = config
-                .internal_directory_path
-                .join(cache_entry.path.clone());
-            let output_full_path = config.target_directory_path.join(&cache_entry.path);
-            if cache_entry.path.has_root() || cache_entry.path.parse_dot()?.starts_with("..") {
-                return Err(AssetError::new(AssetErrorType::AssetPathError(
-                    cache_entry.path,
-                )));
+/// Return paths of all files in directory (recursively) relative to it, sorted.
+fn directory_files(directory_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    list_files(directory_path, Path::new(""), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Copy files (paths relative to source directory) to output directory, creating subdirectories.
+fn copy_files(
+    source_directory_path: &Path,
+    output_directory_path: &Path,
+    files: &[PathBuf],
+    preserve_metadata: bool,
+) -> io::Result<()> {
+    create_dir_all(output_directory_path)?;
+    for file in files {
+        let file_full_path = output_directory_path.join(file);
+        if let Some(file_full_path_parent) = file_full_path.parent() {
+            create_dir_all(file_full_path_parent)?;
+        }
+        let source_full_path = source_directory_path.join(file);
+        copy(&source_full_path, &file_full_path)?;
+        if preserve_metadata {
+            copy_metadata(&source_full_path, &file_full_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Publish files of directory asset to asset directory in every target directory. Directory with missing files is published again. Banner, stable alias, precompressed variants and integrity are not supported for directory assets.
+fn publish_directory<E>(
+    cache_entry: &AssetCacheEntry,
+    changed: bool,
+    context: &mut PackContext<E>,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    let config = context.config;
+    let source_full_path = config.internal_directory_path.join(&cache_entry.path);
+    for target_directory_path in config.target_directory_paths() {
+        let output_full_path = target_directory_path.join(&cache_entry.path);
+        let already_published =
+            cache_entry.output_hash.is_some() && context.published.contains(&output_full_path);
+        let output_is_stale = (changed && !already_published)
+            || !cache_entry
+                .files
+                .iter()
+                .all(|file| output_full_path.join(file).exists());
+        if !output_is_stale {
+            continue;
+        }
+
+        debug!(
+            "Publishing directory {:?} to {:?} ({:?})",
+            source_full_path, output_full_path, config.publish_mode
+        );
+        if output_full_path.is_dir() {
+            remove_dir_all(&output_full_path)?;
+        }
+        for file in &cache_entry.files {
+            let file_source_full_path = source_full_path.join(file);
+            let file_output_full_path = output_full_path.join(file);
+            if let Some(file_output_full_path_parent) = file_output_full_path.parent() {
+                create_dir_all(file_output_full_path_parent)?;
             }
-            debug!("Copying {:?} to {:?}", source_full_path, output_full_path);
-            if let Some(output_full_path_parent) = output_full_path.parent() {
-                create_dir_all(output_full_path_parent)?;
+            let copied = publish_file(
+                &file_source_full_path,
+                &file_output_full_path,
+                config.publish_mode,
+            )?;
+            // Internal files already have metadata of source files. Links share metadata with internal files, so it is not changed through them.
+            if copied {
+                if config.preserve_metadata {
+                    copy_metadata(&file_source_full_path, &file_output_full_path)?;
+                }
+                if let Some(mode) = cache_entry.data.mode {
+                    set_file_mode(&file_output_full_path, mode)?;
+                }
             }
-            copy(&source_full_path, &output_full_path)?;
+            context.report.bytes_written += fs::metadata(&file_output_full_path)?.len();
         }
+        context.published.insert(output_full_path);
+    }
+    Ok(())
+}
 
-        Ok(())
+/// Hash file content by default algorithm (blake3), same as cache entries of assets built with default config. File is read in chunks.
+pub fn hash_file(file_path: &Path) -> io::Result<AssetHash> {
+    content_hash(HashAlgorithm::default(), file_path)
+}
+
+/// Hash bytes by default algorithm (blake3), same as cache entries of assets built with default config.
+pub fn hash_bytes(bytes: &[u8]) -> AssetHash {
+    AssetHash::of_bytes(HashAlgorithm::default(), bytes)
+}
+
+/// Hash file content by given algorithm, reading file in chunks (see `ContentHasher::update_file`). All file hashes of cache entries are made by this function.
+pub fn content_hash(algorithm: HashAlgorithm, file_path: &Path) -> io::Result<AssetHash> {
+    let mut hasher = ContentHasher::new(algorithm);
+    hasher.update_file(file_path)?;
+    Ok(hasher.finalize())
+}
+
+/// Check that file with content-hashed path is absent or has same content. Different content means collision of truncated hashes.
+fn check_hash_collision<E>(
+    name: &str,
+    content_path: &Path,
+    hash: &AssetHash,
+    config: &AssetConfig,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    let content_full_path = config.internal_directory_path.join(content_path);
+    let existing_hash = if content_full_path.is_dir() {
+        // Directory and glob assets are identified by combined hash of their files.
+        let files = directory_files(&content_full_path)?;
+        Some(hash_files(hash.algorithm, &content_full_path, &files)?)
+    } else if content_full_path.exists() {
+        Some(content_hash(hash.algorithm, &content_full_path)?)
+    } else {
+        None
+    };
+    if existing_hash.is_some_and(|existing_hash| &existing_hash != hash) {
+        return Err(AssetError::new(AssetErrorType::OutputHashCollision {
+            asset: name.to_string(),
+            path: content_path.to_path_buf(),
+        }));
+    }
+    Ok(())
+}
+
+/// Rebuild reason of filtered asset with rebuilt inputs.
+const INPUTS_REBUILT_REASON: &str = "input assets were rebuilt";
+
+/// Result of checking cached asset before resolving its inputs.
+enum StaleCheck {
+    Rebuild(&'static str),
+    UpToDate,
+    /// Filtered asset definition is unchanged, decision depends on inputs.
+    CheckInputs(AssetFiltered),
+}
+
+/// Create (or replace) alias for file in same directory.
+fn create_alias(file_path: &Path, alias_path: &Path, stable_alias: StableAlias) -> io::Result<()> {
+    if alias_path.symlink_metadata().is_ok() {
+        remove_file(alias_path)?;
     }
 
-    pub fn get_entry(&self, name: &str) -> Option<AssetCacheEntry> {
-        return self.map.get(name).cloned();
+    if stable_alias == StableAlias::Symlink {
+        if let Some(file_name) = file_path.file_name() {
+            match create_symlink(Path::new(file_name), alias_path) {
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => {}
+                result => return result,
+            }
+        }
+    }
+
+    copy(file_path, alias_path).map(|_| ())
+}
+
+/// Create symbolic link to file. Return `Unsupported` error on platforms without symbolic links.
+fn create_symlink(file_path: &Path, link_path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(file_path, link_path);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(file_path, link_path);
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (file_path, link_path);
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// Copy modification time and permissions of file to another file.
+fn copy_metadata(file_path: &Path, other_file_path: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(file_path)?;
+    // Modification time is set first, as permissions can make file read-only.
+    File::options()
+        .write(true)
+        .open(other_file_path)?
+        .set_modified(metadata.modified()?)?;
+    fs::set_permissions(other_file_path, metadata.permissions())
+}
+
+/// Write compressed copy of file.
+fn compress_file(
+    file_path: &Path,
+    variant_path: &Path,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut file = File::open(file_path)?;
+    let variant_file = io::BufWriter::new(File::create(variant_path)?);
+    match compression {
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(variant_file, flate2::Compression::best());
+            io::copy(&mut file, &mut encoder)?;
+            encoder.finish()?.flush()?;
+        }
+        Compression::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(variant_file, 4096, 11, 22);
+            io::copy(&mut file, &mut encoder)?;
+            encoder.into_inner().flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Place internal file at output path according to publish mode. If link can not be created, file is copied. Return `true` if output is a copy.
+fn publish_file(
+    file_path: &Path,
+    output_path: &Path,
+    publish_mode: PublishMode,
+) -> io::Result<bool> {
+    let result = match publish_mode {
+        PublishMode::Copy => return copy(file_path, output_path).map(|_| true),
+        PublishMode::Symlink => create_symlink(&fs::canonicalize(file_path)?, output_path),
+        PublishMode::Hardlink => fs::hard_link(file_path, output_path),
+    };
+    if let Err(err) = result {
+        warn!(
+            "Failed to link {:?} to {:?} ({}), copying it instead",
+            output_path, file_path, err
+        );
+        copy(file_path, output_path)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Set permissions of file (Unix only, does nothing on other platforms).
+fn set_file_mode(file_path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(file_path, fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file_path, mode);
+        Ok(())
+    }
+}
+
+/// Compute hash of filter name and filter options passed to filter (with environment variables expanded if `expand_env_vars` is set in config), so changed variable values cause rebuild.
+fn fingerprint_filter_options<E>(
+    filtered: &AssetFiltered,
+    config: &AssetConfig,
+) -> AssetResult<AssetHash, E>
+where
+    E: AssetFilterError,
+{
+    fingerprint_options(
+        &filtered.filter_name,
+        filter_options(&filtered.options, config)?.as_ref(),
+    )
+}
+
+/// Compute hash of filter name and filter options. Options are serialized as JSON with sorted keys. Fingerprints are always made by blake3, as they are not compared with file hashes.
+fn fingerprint_options<E>(
+    filter_name: &str,
+    options: &HashMap<String, AssetFilterOption>,
+) -> AssetResult<AssetHash, E>
+where
+    E: AssetFilterError,
+{
+    let options = serde_json::to_string(&serde_json::to_value(options)?)?;
+
+    let mut hasher = ContentHasher::new(HashAlgorithm::Blake3);
+    hasher.update(filter_name.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(options.as_bytes());
+    Ok(hasher.finalize())
+}
+
+/// Compute combined hash of input cache entries. Entries with stored content hash are identified by name and content hash only, so inputs rebuilt with identical content keep fingerprint.
+fn fingerprint_inputs(input_entries: &[AssetCacheEntry]) -> AssetHash {
+    let mut hasher = ContentHasher::new(HashAlgorithm::Blake3);
+    for input_entry in input_entries {
+        hasher.update(input_entry.name.as_bytes());
+        hasher.update(&[0]);
+        if let Some(file_hash) = &input_entry.file_hash {
+            hasher.update(&[2]);
+            hasher.update(&file_hash.hash);
+            continue;
+        }
+        hasher.update(input_entry.path.to_string_lossy().as_bytes());
+        hasher.update(&[0]);
+        for hash in [
+            &input_entry.file_hash,
+            &input_entry.options_fingerprint,
+            &input_entry.inputs_fingerprint,
+        ] {
+            match hash {
+                Some(hash) => {
+                    hasher.update(&[1]);
+                    hasher.update(&hash.hash);
+                }
+                None => {
+                    hasher.update(&[0]);
+                }
+            }
+        }
+    }
+    hasher.finalize()
+}
+
+/// Compute Subresource Integrity string of public asset file with given internal file content (banner is prepended like when copying to target directory).
+fn integrity_with_banner<R>(
+    algorithm: IntegrityAlgorithm,
+    data: &AssetData,
+    content: R,
+) -> io::Result<String>
+where
+    R: Read,
+{
+    let banner_comment = data
+        .banner
+        .as_ref()
+        .and_then(|banner| format_banner(banner, &data.extension))
+        .unwrap_or_default();
+    algorithm.integrity_of_reader(banner_comment.as_bytes().chain(content))
+}
+
+/// Format banner as comment for file with given extension. Return `None` if extension does not support comments.
+pub(crate) fn format_banner(banner: &str, extension: &str) -> Option<String> {
+    match extension.to_lowercase().as_str() {
+        "css" | "scss" | "less" | "js" | "mjs" | "cjs" | "ts" => {
+            Some(format!("/*! {} */\n", banner.replace("*/", "* /")))
+        }
+        "sass" => Some(
+            banner
+                .lines()
+                .map(|line| format!("// {}\n", line))
+                .collect(),
+        ),
+        "html" | "htm" => Some(format!("<!-- {} -->\n", banner.replace("--", "- -"))),
+        _ => None,
+    }
+}
+
+/// Difference between two cache manifests. All name lists are sorted.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheDiff {
+    /// Names of entries present only in new cache manifest.
+    pub added: Vec<String>,
+    /// Names of entries present only in old cache manifest.
+    pub removed: Vec<String>,
+    /// Names of entries present in both cache manifests, but with different output path or hash (i.e. rebuilt).
+    pub changed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AssetCacheManifestVersioned {
+    V1(AssetCacheManifestV1),
+}
+
+/// Borrowed counterpart of `AssetCacheManifestVersioned` used to save cache manifest without cloning it.
+#[derive(Serialize)]
+pub(crate) enum AssetCacheManifestVersionedRef<'a> {
+    V1(&'a AssetCacheManifestV1),
+}
+
+impl Default for AssetCacheManifestVersioned {
+    fn default() -> Self {
+        AssetCacheManifestVersioned::V1(AssetCacheManifestV1::default())
+    }
+}
+
+/// Asset cache manifest. It contains current file paths, data to check if assets are modified, etc.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetCacheManifestV1 {
+    pub map: std::collections::HashMap<String, AssetCacheEntry>,
+    /// Paths of public asset files relative to target directory, rebuilt after public assets are processed.
+    #[serde(default)]
+    pub public_index: std::collections::HashMap<String, PathBuf>,
+    /// Build generation, increased after every successful pack run.
+    #[serde(default)]
+    generation: u64,
+}
+
+impl AssetCacheManifestV1 {
+    /// Return build generation. It is increased after every successful pack run, so readers can detect new build and reload cache manifest.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Increase build generation.
+    pub fn bump_generation(&mut self) {
+        self.generation += 1;
+    }
+
+    pub fn process<E>(
+        &mut self,
+        name: String,
+        context: &mut PackContext<E>,
+    ) -> AssetResult<(AssetCacheEntry, bool), E>
+    where
+        E: AssetFilterError,
+    {
+        if let Some(cycle) = cycle_in_path(&context.visiting, &name) {
+            return Err(AssetError::new(AssetErrorType::DependencyCycle(cycle)));
+        }
+        // Inputs are processed recursively, so depth is limited to protect stack.
+        if context.visiting.len() >= context.config.max_dependency_depth {
+            return Err(AssetError::new(AssetErrorType::DependencyTooDeep {
+                asset: name,
+                depth: context.visiting.len() + 1,
+            }));
+        }
+        context.visiting.push(name.clone());
+        let result = self.process_entry(name, context);
+        context.visiting.pop();
+        result
+    }
+
+    fn process_entry<E>(
+        &mut self,
+        name: String,
+        context: &mut PackContext<E>,
+    ) -> AssetResult<(AssetCacheEntry, bool), E>
+    where
+        E: AssetFilterError,
+    {
+        if let Some(data) = context.manifest.assets.get(&name) {
+            if !data.is_enabled(&context.config.enabled_features) {
+                return Err(AssetError::new(AssetErrorType::AssetDisabledError(name)));
+            }
+        }
+
+        // Asset already resolved in this run (for example, input shared by several assets) is not checked again.
+        if let (Some(&changed), Some(cache_entry)) =
+            (context.resolved.get(&name), self.map.get(&name))
+        {
+            return Ok((cache_entry.clone(), changed));
+        }
+
+        let first_resolution = !context.is_resolved(&name);
+        let observer = context.observer.filter(|_| first_resolution);
+        if let Some(observer) = observer {
+            observer.on_asset_start(&name);
+        }
+
+        let cache_entry_optional = self.map.get(&name).cloned();
+        let (mut cache_entry, changed) = match cache_entry_optional {
+            Some(cache_entry) => {
+                if let Some(cache_entry_new) = cache_entry.update(self, context)? {
+                    self.map.insert(name.clone(), cache_entry_new.clone());
+                    (cache_entry_new, true)
+                } else {
+                    (cache_entry, false)
+                }
+            }
+            None => {
+                let cache_entry = AssetCacheEntry::create(name.clone(), self, context)?;
+                self.map.insert(name.clone(), cache_entry.clone());
+                (cache_entry, true)
+            }
+        };
+
+        let is_public = context.manifest.public_assets.contains(&name);
+        if cache_entry.is_public != is_public {
+            cache_entry.is_public = is_public;
+            if let Some(entry) = self.map.get_mut(&name) {
+                entry.is_public = is_public;
+            }
+        }
+
+        // Asset could be already rebuilt earlier in this run (e.g. as input of another asset).
+        let changed = context.record_resolved(&name, changed);
+        if first_resolution {
+            if changed {
+                let bytes = cache_entry
+                    .input_full_paths(context.config)
+                    .iter()
+                    .filter_map(|full_path| fs::metadata(full_path).ok())
+                    .map(|metadata| metadata.len())
+                    .sum();
+                context.report.rebuilt.push(name.clone());
+                context.report.bytes_written += bytes;
+                if let Some(observer) = observer {
+                    observer.on_asset_built(&name, bytes);
+                }
+            } else {
+                context.report.skipped.push(name.clone());
+                if let Some(observer) = observer {
+                    observer.on_asset_skipped(&name);
+                }
+            }
+        }
+        Ok((cache_entry, changed))
+    }
+
+    pub fn process_public_assets<E>(
+        &mut self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistry<E>,
+    ) -> AssetResult<PackReport, E>
+    where
+        E: AssetFilterError,
+    {
+        self.process_public_assets_matching(config, manifest, filter_registry, &|_| true)
+    }
+
+    /// Compute plan of rebuilding public assets: check all assets like `process_public_assets` does, but without writing files or changing cache manifest. Disabled public assets are skipped.
+    pub fn plan<E>(
+        &self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistry<E>,
+    ) -> AssetResult<BuildPlan, E>
+    where
+        E: AssetFilterError,
+    {
+        let context = PackContext::new(config, manifest, filter_registry);
+        self.plan_public_asset_names(&manifest.public_assets, &context)
+    }
+
+    /// Compute plan of rebuilding given public assets, see `plan`.
+    pub fn plan_public_asset_names<E>(
+        &self,
+        names: &[String],
+        context: &PackContext<E>,
+    ) -> AssetResult<BuildPlan, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut plan = BuildPlan::default();
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut path: Vec<&str> = Vec::new();
+        for name in names {
+            let enabled = context
+                .manifest
+                .assets
+                .get(name)
+                .is_none_or(|data| data.is_enabled(&context.config.enabled_features));
+            if enabled {
+                self.plan_asset(name, &mut path, context, &mut plan, &mut visited)?;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Add asset to plan if it should be rebuilt (after its inputs). Return `true` if asset should be rebuilt.
+    ///
+    /// `path` contains names of assets being planned (each asset uses next one as input).
+    fn plan_asset<'n, E>(
+        &self,
+        name: &'n str,
+        path: &mut Vec<&'n str>,
+        context: &'n PackContext<E>,
+        plan: &mut BuildPlan,
+        visited: &mut HashSet<String>,
+    ) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        if let Some(cycle) = cycle_in_path(path, name) {
+            return Err(AssetError::new(AssetErrorType::DependencyCycle(cycle)));
+        }
+        if path.len() >= context.config.max_dependency_depth {
+            return Err(AssetError::new(AssetErrorType::DependencyTooDeep {
+                asset: name.to_string(),
+                depth: path.len() + 1,
+            }));
+        }
+        if !visited.insert(name.to_string()) {
+            return Ok(plan.reason(name).is_some());
+        }
+        path.push(name);
+        let result = self.plan_entry(name, path, context, plan, visited);
+        path.pop();
+        result
+    }
+
+    fn plan_entry<'n, E>(
+        &self,
+        name: &'n str,
+        path: &mut Vec<&'n str>,
+        context: &'n PackContext<E>,
+        plan: &mut BuildPlan,
+        visited: &mut HashSet<String>,
+    ) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        let data = context.manifest.assets.get(name).ok_or_else(|| {
+            AssetError::new(AssetErrorType::AssetNotFoundInManifestError(
+                name.to_string(),
+            ))
+        })?;
+        if !data.is_enabled(&context.config.enabled_features) {
+            return Err(AssetError::new(AssetErrorType::AssetDisabledError(
+                name.to_string(),
+            )));
+        }
+
+        let rebuild_reason = match self.map.get(name) {
+            None => {
+                self.plan_inputs(data, path, context, plan, visited)?;
+                Some("asset is not built yet")
+            }
+            Some(cache_entry) => match cache_entry.check_stale(data, context)? {
+                StaleCheck::Rebuild(reason) => {
+                    self.plan_inputs(data, path, context, plan, visited)?;
+                    Some(reason)
+                }
+                StaleCheck::UpToDate => None,
+                StaleCheck::CheckInputs(filtered) => {
+                    let has_updated_inputs =
+                        self.plan_inputs(data, path, context, plan, visited)?;
+                    if has_updated_inputs {
+                        // Content of rebuilt inputs is not known yet, it is checked when asset is processed.
+                        Some(INPUTS_REBUILT_REASON)
+                    } else {
+                        let input_entries: Vec<AssetCacheEntry> = filtered
+                            .input_names
+                            .iter()
+                            .filter_map(|input_name| self.map.get(input_name).cloned())
+                            .collect();
+                        cache_entry.inputs_rebuild_reason(
+                            &filtered,
+                            &input_entries,
+                            false,
+                            context.config,
+                        )?
+                    }
+                }
+            },
+        };
+
+        let rebuild_reason = match (context.should_rebuild, self.map.get(name)) {
+            (Some(should_rebuild), Some(cache_entry)) => match should_rebuild(cache_entry, data) {
+                Some(true) => Some("rebuild requested by callback"),
+                Some(false)
+                    if context
+                        .config
+                        .internal_directory_path
+                        .join(&cache_entry.path)
+                        .exists() =>
+                {
+                    None
+                }
+                _ => rebuild_reason,
+            },
+            _ => rebuild_reason,
+        };
+
+        match rebuild_reason {
+            Some(reason) => {
+                plan.rebuilds.push(PlannedRebuild {
+                    name: name.to_string(),
+                    reason: reason.to_string(),
+                    tentative: reason == INPUTS_REBUILT_REASON,
+                });
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Plan inputs of filtered asset. Return `true` if any input should be rebuilt.
+    fn plan_inputs<'n, E>(
+        &self,
+        data: &'n AssetData,
+        path: &mut Vec<&'n str>,
+        context: &'n PackContext<E>,
+        plan: &mut BuildPlan,
+        visited: &mut HashSet<String>,
+    ) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut has_updated_inputs = false;
+        if let AssetSource::Filtered(filtered) = &data.source {
+            for input_name in &filtered.input_names {
+                has_updated_inputs |= self.plan_asset(input_name, path, context, plan, visited)?;
+            }
+        }
+        Ok(has_updated_inputs)
+    }
+
+    /// Process public assets according to build plan made by `plan` (without checking assets again), copy them to target directory.
+    pub fn apply<E>(
+        &mut self,
+        plan: &BuildPlan,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistry<E>,
+    ) -> AssetResult<PackReport, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut context = PackContext::new(config, manifest, filter_registry);
+        context.plan = Some(plan);
+        self.process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)?;
+        Ok(context.report)
+    }
+
+    /// Same as `process_public_assets`, but notify observer when assets are processed.
+    pub fn process_public_assets_with_observer<E>(
+        &mut self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistry<E>,
+        observer: &dyn PackObserver,
+    ) -> AssetResult<PackReport, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut context = PackContext::new(config, manifest, filter_registry);
+        context.observer = Some(observer);
+        self.process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)?;
+        Ok(context.report)
+    }
+
+    /// Process all public assets, but copy to target directory only public assets with names matching predicate.
+    ///
+    /// Non-matching public assets are still built into internal directory, so cache manifest stays consistent.
+    pub fn process_public_assets_matching<E>(
+        &mut self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistry<E>,
+        predicate: &dyn Fn(&str) -> bool,
+    ) -> AssetResult<PackReport, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut context = PackContext::new(config, manifest, filter_registry);
+        self.process_public_asset_names(&manifest.public_assets, predicate, &mut context)?;
+        Ok(context.report)
+    }
+
+    /// Process given public assets, copy to target directory only assets with names matching predicate.
+    ///
+    /// Public assets with required features not enabled are skipped, and their files and cache entries are removed. If context has checkpoint path, cache manifest is saved after every rebuilt or removed asset and on failure.
+    pub fn process_public_asset_names<E>(
+        &mut self,
+        names: &[String],
+        predicate: &dyn Fn(&str) -> bool,
+        context: &mut PackContext<E>,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        check_public_asset_names(names, context)?;
+        for asset_name in names {
+            let enabled = context
+                .manifest
+                .assets
+                .get(asset_name)
+                .is_none_or(|data| data.is_enabled(&context.config.enabled_features));
+
+            let result = if !enabled {
+                debug!("Skipping asset {} with disabled features", asset_name);
+                self.remove_entry(asset_name, context.config)
+            } else if predicate(asset_name) {
+                self.process_public_asset(asset_name.clone(), context)
+            } else {
+                self.process(asset_name.clone(), context)
+                    .map(|(_, changed)| changed)
+            };
+
+            match result {
+                Ok(changed) => {
+                    if changed {
+                        self.checkpoint(context)?;
+                    }
+                }
+                Err(err) => {
+                    self.checkpoint(context)?;
+                    return Err(err);
+                }
+            }
+        }
+
+        // Assets are removed after processing, so entries of inputs still used are kept.
+        context.report.removed = self.remove_undefined_entries(context.manifest, context.config)?;
+        if !context.report.removed.is_empty() {
+            self.checkpoint(context)?;
+        }
+
+        self.update_public_index();
+
+        context.report.duplicate_outputs = self.duplicate_public_outputs(context.config)?;
+        for group in &context.report.duplicate_outputs {
+            warn!("Public assets {:?} have identical output content", group);
+        }
+
+        if context.config.auto_prune {
+            context.report.pruned = self.prune(context.config, context.manifest)?;
+        }
+
+        Ok(())
+    }
+
+    /// Find groups of public assets with identical output content. Stored source hashes are used when available, other internal files are hashed. Entries with missing internal files are skipped.
+    pub fn duplicate_public_outputs(&self, config: &AssetConfig) -> io::Result<Vec<Vec<String>>> {
+        let mut groups: HashMap<AssetHash, Vec<String>> = HashMap::new();
+        for (name, cache_entry) in &self.map {
+            if !cache_entry.is_public {
+                continue;
+            }
+            let hash = match cache_entry
+                .output_hash
+                .as_ref()
+                .or(cache_entry.file_hash.as_ref())
+            {
+                Some(hash) => hash.clone(),
+                None => {
+                    let internal_full_path = config.internal_directory_path.join(&cache_entry.path);
+                    if !internal_full_path.is_file() {
+                        continue;
+                    }
+                    content_hash(config.hash_algorithm, &internal_full_path)?
+                }
+            };
+            groups.entry(hash).or_default().push(name.clone());
+        }
+
+        let mut duplicates: Vec<Vec<String>> = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort();
+                group
+            })
+            .collect();
+        duplicates.sort();
+        Ok(duplicates)
+    }
+
+    /// Remove cache entries of assets not reachable from enabled public assets of manifest, then remove files in internal and target directories not referenced by remaining entries (including their aliases and precompressed variants). Return sorted full paths of removed files.
+    ///
+    /// Internal and target directories should contain only files managed by crate, other files are removed too. Empty subdirectories are removed, symbolic links are not followed.
+    pub fn prune(
+        &mut self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+    ) -> io::Result<Vec<PathBuf>> {
+        let public_assets: Vec<String> = manifest
+            .public_assets
+            .iter()
+            .filter(|name| {
+                manifest
+                    .assets
+                    .get(*name)
+                    .is_some_and(|data| data.is_enabled(&config.enabled_features))
+            })
+            .cloned()
+            .collect();
+        let reachable = DependencyGraph::new(manifest).with_inputs(&public_assets);
+        self.map.retain(|name, _| {
+            let is_reachable = reachable.contains(name);
+            if !is_reachable {
+                debug!("Pruning cache entry {}", name);
+            }
+            is_reachable
+        });
+        self.update_public_index();
+
+        let internal_paths: HashSet<PathBuf> = self
+            .map
+            .values()
+            .flat_map(|cache_entry| match cache_entry.data.source {
+                AssetSource::Glob(_) | AssetSource::Directory(_) => cache_entry
+                    .files
+                    .iter()
+                    .map(|file| cache_entry.path.join(file))
+                    .collect(),
+                _ => vec![cache_entry.path.clone()],
+            })
+            .collect();
+        let mut target_paths: HashSet<PathBuf> = HashSet::new();
+        for cache_entry in self.map.values() {
+            if let AssetSource::Directory(_) = cache_entry.data.source {
+                target_paths.extend(
+                    cache_entry
+                        .files
+                        .iter()
+                        .map(|file| cache_entry.path.join(file)),
+                );
+            }
+            target_paths.insert(cache_entry.path.clone());
+            for (_, extension) in CONTENT_ENCODINGS.iter() {
+                target_paths.insert(append_extension(&cache_entry.path, extension));
+            }
+            if let Some(alias_path) = &cache_entry.alias_path {
+                target_paths.insert(alias_path.clone());
+            }
+        }
+
+        let mut pruned: Vec<PathBuf> = Vec::new();
+        prune_directory(
+            &config.internal_directory_path,
+            |path| internal_paths.contains(path) || path == Path::new(LOCK_FILE_NAME),
+            &mut pruned,
+        )?;
+        for target_directory_path in config.target_directory_paths() {
+            prune_directory(
+                target_directory_path,
+                |path| target_paths.contains(path),
+                &mut pruned,
+            )?;
+        }
+        pruned.sort();
+        Ok(pruned)
+    }
+
+    /// Rebuild public asset path index from cache entries.
+    pub fn update_public_index(&mut self) {
+        self.public_index = self
+            .map
+            .values()
+            .filter(|cache_entry| cache_entry.is_public)
+            .map(|cache_entry| (cache_entry.name.clone(), cache_entry.path.clone()))
+            .collect();
+    }
+
+    /// Write public manifest: pretty-printed JSON dictionary mapping public asset names (sorted) to file paths relative to target directory. Assets with unsafe paths are skipped.
+    pub fn write_public_manifest(&self, path: &Path, config: &AssetConfig) -> io::Result<()> {
+        let public_manifest: BTreeMap<&String, &PathBuf> = self
+            .map
+            .values()
+            .filter(|cache_entry| {
+                cache_entry.is_public && self.target_full_path(&cache_entry.name, config).is_some()
+            })
+            .map(|cache_entry| (&cache_entry.name, &cache_entry.path))
+            .collect();
+
+        let public_manifest_file = File::create(path)?;
+        serde_json::to_writer_pretty(public_manifest_file, &public_manifest)?;
+        Ok(())
+    }
+
+    /// Return path of public asset file relative to target directory using public index.
+    pub fn public_path(&self, name: &str) -> Option<&Path> {
+        self.public_index.get(name).map(PathBuf::as_path)
+    }
+
+    /// Save cache manifest to context checkpoint path, if it is set.
+    pub(crate) fn checkpoint<E>(&self, context: &PackContext<E>) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        match context.checkpoint_path {
+            Some(checkpoint_path) => self.save(checkpoint_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Return number of cache entries with given file path. With `ContentHash` naming strategy assets may share files, such files are removed only when no entry uses them.
+    pub fn path_references(&self, path: &Path) -> usize {
+        self.map
+            .values()
+            .filter(|cache_entry| cache_entry.path == path)
+            .count()
+    }
+
+    /// Remove cache entry with given name and its files from internal and target directories (files shared with other entries are kept). Entries of its inputs are not removed. Return `true` if entry existed.
+    pub fn remove_entry<E>(&mut self, name: &str, config: &AssetConfig) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        match self.map.remove(name) {
+            Some(cache_entry) => {
+                if self.path_references(&cache_entry.path) > 0 {
+                    debug!("Keeping {:?} used by other assets", cache_entry.path);
+                } else {
+                    cache_entry.remove_files(config)?;
+                }
+                self.public_index.remove(name);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Remove cache entries (and their files) of assets not defined in manifest. Return sorted names of removed entries.
+    pub fn remove_undefined_entries<E>(
+        &mut self,
+        manifest: &AssetManifest,
+        config: &AssetConfig,
+    ) -> AssetResult<Vec<String>, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut removed: Vec<String> = self
+            .map
+            .keys()
+            .filter(|name| !manifest.assets.contains_key(*name))
+            .cloned()
+            .collect();
+        removed.sort();
+        for name in &removed {
+            debug!("Removing asset {} not defined in manifest", name);
+            self.remove_entry(name, config)?;
+        }
+        Ok(removed)
+    }
+
+    /// Save cache manifest to file. Manifest is written to temporary file in same directory and then renamed, so file always contains either old or new complete manifest.
+    pub fn save<E>(&self, cache_manifest_path: &Path) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let mut temp_file_name = std::ffi::OsString::from(".");
+        temp_file_name.push(cache_manifest_path.file_name().ok_or_else(|| {
+            AssetError::new(AssetErrorType::AssetPathError(
+                cache_manifest_path.to_path_buf(),
+            ))
+        })?);
+        temp_file_name.push(format!(".{}.tmp", Uuid::new_v4()));
+        let temp_path = cache_manifest_path.with_file_name(temp_file_name);
+
+        let result = self.write_to_file(&temp_path).and_then(|_| {
+            fs::rename(&temp_path, cache_manifest_path)?;
+            Ok(())
+        });
+        if result.is_err() && temp_path.exists() {
+            remove_file(&temp_path)?;
+        }
+        result
+    }
+
+    /// Write cache manifest to file and flush it to disk.
+    fn write_to_file<E>(&self, path: &Path) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let mut writer = io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &AssetCacheManifestVersionedRef::V1(self))?;
+        let file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Process single public asset and copy it to target directory. Return `true` if asset was rebuilt.
+    pub fn process_public_asset<E>(
+        &mut self,
+        name: String,
+        context: &mut PackContext<E>,
+    ) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        let config = context.config;
+        check_public_asset_names(std::slice::from_ref(&name), context)?;
+        let (cache_entry, changed) = self.process(name.clone(), context)?;
+
+        let source_full_path = config
+            .internal_directory_path
+            .join(cache_entry.path.clone());
+        if !is_safe_relative_path(&cache_entry.path) {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(
+                cache_entry.path,
+            )));
+        }
+        if let AssetSource::Directory(_) = cache_entry.data.source {
+            publish_directory(&cache_entry, changed, context)?;
+            return Ok(changed);
+        }
+
+        let alias_path = cache_entry
+            .data
+            .stable_alias
+            .map(|_| cache_entry.data.file_path(&cache_entry.name));
+        if let Some(alias_path) = &alias_path {
+            if !is_safe_relative_path(alias_path) {
+                return Err(AssetError::new(AssetErrorType::AssetPathError(
+                    alias_path.clone(),
+                )));
+            }
+        }
+
+        for target_directory_path in config.target_directory_paths() {
+            let output_full_path = target_directory_path.join(&cache_entry.path);
+            let alias_full_path = alias_path
+                .as_ref()
+                .map(|alias_path| target_directory_path.join(alias_path));
+
+            // Files in target are named by version, so existing unchanged file is up to date. Content-hashed file shared by assets is copied once per run.
+            let already_published =
+                cache_entry.output_hash.is_some() && context.published.contains(&output_full_path);
+            let output_is_stale = (changed && !already_published) || !output_full_path.exists();
+            if output_is_stale {
+                debug!(
+                    "Publishing {:?} to {:?} ({:?})",
+                    source_full_path, output_full_path, config.publish_mode
+                );
+                if let Some(output_full_path_parent) = output_full_path.parent() {
+                    create_dir_all(output_full_path_parent)?;
+                }
+                // Old output can be link to internal file, so it is removed instead of being overwritten.
+                if output_full_path.symlink_metadata().is_ok() {
+                    remove_file(&output_full_path)?;
+                }
+                let copied = match &cache_entry.data.banner {
+                    Some(banner) => {
+                        let banner_comment = format_banner(banner, &cache_entry.data.extension)
+                            .ok_or_else(|| {
+                                AssetError::new(AssetErrorType::BannerNotSupportedError(
+                                    cache_entry.data.extension.clone(),
+                                ))
+                            })?;
+                        let mut output_file = File::create(&output_full_path)?;
+                        output_file.write_all(banner_comment.as_bytes())?;
+                        io::copy(&mut File::open(&source_full_path)?, &mut output_file)?;
+                        true
+                    }
+                    None => {
+                        publish_file(&source_full_path, &output_full_path, config.publish_mode)?
+                    }
+                };
+                // Internal file of `File` asset already has metadata of source file. Links share metadata with internal file, so it is not changed through them.
+                if copied {
+                    if config.preserve_metadata {
+                        if let AssetSource::File(_) = cache_entry.data.source {
+                            copy_metadata(&source_full_path, &output_full_path)?;
+                        }
+                    }
+                    if let Some(mode) = cache_entry.data.mode {
+                        set_file_mode(&output_full_path, mode)?;
+                    }
+                }
+                context.report.bytes_written += fs::metadata(&output_full_path)?.len();
+                context.published.insert(output_full_path.clone());
+            }
+
+            if let (Some(stable_alias), Some(alias_full_path)) =
+                (cache_entry.data.stable_alias, alias_full_path)
+            {
+                if output_is_stale || alias_full_path.symlink_metadata().is_err() {
+                    debug!(
+                        "Creating alias {:?} for {:?}",
+                        alias_full_path, output_full_path
+                    );
+                    create_alias(&output_full_path, &alias_full_path, stable_alias)?;
+                }
+            }
+
+            // Variants are written again only for new output content.
+            for compression in &config.precompress {
+                let variant_full_path =
+                    append_extension(&output_full_path, compression.extension());
+                let variant_is_stale = output_is_stale
+                    || !cache_entry.precompressed.contains(compression)
+                    || !variant_full_path.exists();
+                if variant_is_stale && !context.published.contains(&variant_full_path) {
+                    debug!(
+                        "Compressing {:?} to {:?} ({:?})",
+                        output_full_path, variant_full_path, compression
+                    );
+                    compress_file(&output_full_path, &variant_full_path, *compression)?;
+                    context.report.bytes_written += fs::metadata(&variant_full_path)?.len();
+                    context.published.insert(variant_full_path);
+                }
+            }
+            for compression in &cache_entry.precompressed {
+                let variant_full_path =
+                    append_extension(&output_full_path, compression.extension());
+                if !config.precompress.contains(compression) && variant_full_path.exists() {
+                    debug!("Removing precompressed variant {:?}", variant_full_path);
+                    remove_file(&variant_full_path)?;
+                }
+            }
+        }
+
+        if let Some(entry) = self.map.get_mut(&name) {
+            entry.precompressed = config.precompress.clone();
+        }
+
+        if let Some(alias_path) = alias_path {
+            if let Some(entry) = self.map.get_mut(&name) {
+                entry.alias_path = Some(alias_path);
+            }
+        }
+
+        // Entries built without integrity (for example, before it was enabled in config) get it here.
+        if let Some(algorithm) = config.integrity {
+            let has_integrity = cache_entry
+                .integrity
+                .as_ref()
+                .is_some_and(|integrity| integrity.starts_with(&format!("{}-", algorithm.name())));
+            if !has_integrity {
+                let integrity = integrity_with_banner(
+                    algorithm,
+                    &cache_entry.data,
+                    File::open(&source_full_path)?,
+                )?;
+                if let Some(entry) = self.map.get_mut(&name) {
+                    entry.integrity = Some(integrity);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+
+    pub fn get_entry(&self, name: &str) -> Option<AssetCacheEntry> {
+        self.map.get(name).cloned()
+    }
+
+    /// Return full path of asset file in internal directory. Return `None` if asset is not found or its path is unsafe.
+    pub fn internal_full_path(&self, name: &str, config: &AssetConfig) -> Option<PathBuf> {
+        self.full_path(name, &config.internal_directory_path)
+    }
+
+    /// Return full path of public asset file in target with given name (or in target directory if name is `None`). Return `None` if asset or target is not found or asset path is unsafe.
+    pub fn get_public_path(
+        &self,
+        name: &str,
+        target_name: Option<&str>,
+        config: &AssetConfig,
+    ) -> Option<PathBuf> {
+        self.full_path(name, config.get_target_directory_path(target_name)?)
+    }
+
+    /// Return full path of asset file in target directory. Return `None` if asset is not found or its path is unsafe.
+    pub fn target_full_path(&self, name: &str, config: &AssetConfig) -> Option<PathBuf> {
+        self.full_path(name, &config.target_directory_path)
+    }
+
+    /// Write content of public asset to writer, as it is published to target directory. If target file is missing, content is built from internal file. Return number of bytes written.
+    pub fn copy_public_to<W, E>(
+        &self,
+        name: &str,
+        config: &AssetConfig,
+        writer: &mut W,
+    ) -> AssetResult<u64, E>
+    where
+        W: Write + ?Sized,
+        E: AssetFilterError,
+    {
+        let cache_entry = self
+            .map
+            .get(name)
+            .filter(|cache_entry| cache_entry.is_public)
+            .ok_or_else(|| {
+                AssetError::new(AssetErrorType::AssetNotFoundInManifestError(
+                    name.to_string(),
+                ))
+            })?;
+        if !is_safe_relative_path(&cache_entry.path) {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(
+                cache_entry.path.clone(),
+            )));
+        }
+
+        let target_full_path = config.target_directory_path.join(&cache_entry.path);
+        if target_full_path.is_file() {
+            return Ok(io::copy(&mut File::open(&target_full_path)?, writer)?);
+        }
+
+        let internal_full_path = config.internal_directory_path.join(&cache_entry.path);
+        let mut written = 0;
+        if let Some(banner) = &cache_entry.data.banner {
+            let banner_comment =
+                format_banner(banner, &cache_entry.data.extension).ok_or_else(|| {
+                    AssetError::new(AssetErrorType::BannerNotSupportedError(
+                        cache_entry.data.extension.clone(),
+                    ))
+                })?;
+            writer.write_all(banner_comment.as_bytes())?;
+            written += banner_comment.len() as u64;
+        }
+        written += io::copy(&mut File::open(&internal_full_path)?, writer)?;
+        Ok(written)
+    }
+
+    fn full_path(&self, name: &str, directory_path: &Path) -> Option<PathBuf> {
+        self.map
+            .get(name)
+            .filter(|cache_entry| is_safe_relative_path(&cache_entry.path))
+            .map(|cache_entry| directory_path.join(&cache_entry.path))
+    }
+
+    /// Rename files of all entries according to new naming strategy and update entry paths.
+    ///
+    /// Files are renamed one by one, entries with missing internal files are skipped (they are rebuilt on next pack run). Entries already named according to strategy are not changed, so migration can be safely repeated (for example, after error). Cache manifest should be saved after migration, and `naming` in config should be set to new strategy.
+    pub fn migrate_naming<E>(
+        &mut self,
+        config: &AssetConfig,
+        new_strategy: NamingStrategy,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let mut names: Vec<String> = self.map.keys().cloned().collect();
+        names.sort();
+
+        for name in names {
+            let entry = &self.map[&name];
+            if !is_safe_relative_path(&entry.path) {
+                return Err(AssetError::new(AssetErrorType::AssetPathError(
+                    entry.path.clone(),
+                )));
+            }
+            let internal_full_path = config.internal_directory_path.join(&entry.path);
+            if !internal_full_path.exists() {
+                continue;
+            }
+
+            let output_hash = match (&entry.data.source, &entry.file_hash) {
+                (AssetSource::Glob(_) | AssetSource::Directory(_), Some(files_hash)) => {
+                    files_hash.clone()
+                }
+                _ => content_hash(config.hash_algorithm, &internal_full_path)?,
+            };
+            let content_path = entry
+                .data
+                .content_path(&entry.name, &output_hash.hash, config);
+            let new_path = match new_strategy {
+                NamingStrategy::ContentHash => {
+                    check_hash_collision(&entry.name, &content_path, &output_hash, config)?;
+                    content_path
+                }
+                NamingStrategy::Uuid if entry.path == content_path => {
+                    entry
+                        .data
+                        .output_path(&entry.name, &Uuid::new_v4().to_string(), config)
+                }
+                NamingStrategy::Uuid => continue,
+            };
+            if new_path == entry.path {
+                continue;
+            }
+            if !is_safe_relative_path(&new_path) {
+                return Err(AssetError::new(AssetErrorType::AssetPathError(new_path)));
+            }
+
+            entry.rename_files(&new_path, config)?;
+            if let Some(entry) = self.map.get_mut(&name) {
+                entry.path = new_path;
+                entry.output_hash = match new_strategy {
+                    NamingStrategy::ContentHash => Some(output_hash),
+                    NamingStrategy::Uuid => None,
+                };
+            }
+        }
+
+        self.update_public_index();
+
+        Ok(())
+    }
+
+    /// Restore missing internal files from target directory. Return sorted names of entries with missing internal files that could not be restored (they are rebuilt on next pack run).
+    ///
+    /// Only files of assets loaded from source files with stored hash are restored, and only if hash of target file matches. Filtered assets and assets without stored hash are always rebuilt.
+    pub fn rehydrate_internal<E>(&self, config: &AssetConfig) -> AssetResult<Vec<String>, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut not_restored: Vec<String> = Vec::new();
+
+        for (name, cache_entry) in &self.map {
+            let internal_full_path = match self.internal_full_path(name, config) {
+                Some(internal_full_path) => internal_full_path,
+                None => {
+                    not_restored.push(name.clone());
+                    continue;
+                }
+            };
+            if internal_full_path.exists() {
+                continue;
+            }
+
+            let target_full_path = config.target_directory_path.join(&cache_entry.path);
+            let restorable = match (&cache_entry.data.source, &cache_entry.file_hash) {
+                (AssetSource::File(_), Some(file_hash)) if target_full_path.is_file() => {
+                    &content_hash(file_hash.algorithm, &target_full_path)? == file_hash
+                }
+                _ => false,
+            };
+
+            if restorable {
+                debug!(
+                    "Restoring {:?} from {:?}",
+                    internal_full_path, target_full_path
+                );
+                if let Some(internal_full_path_parent) = internal_full_path.parent() {
+                    create_dir_all(internal_full_path_parent)?;
+                }
+                copy(&target_full_path, &internal_full_path)?;
+            } else {
+                not_restored.push(name.clone());
+            }
+        }
+
+        not_restored.sort();
+        Ok(not_restored)
+    }
+
+    /// Compare this cache manifest (old build) with other cache manifest (new build).
+    pub fn diff(&self, other: &Self) -> CacheDiff {
+        let mut diff = CacheDiff::default();
+
+        for (name, entry) in &self.map {
+            match other.map.get(name) {
+                Some(other_entry) => {
+                    if entry.path != other_entry.path || entry.file_hash != other_entry.file_hash {
+                        diff.changed.push(name.clone());
+                    }
+                }
+                None => diff.removed.push(name.clone()),
+            }
+        }
+        for name in other.map.keys() {
+            if !self.map.contains_key(name) {
+                diff.added.push(name.clone());
+            }
+        }
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
     }
 }
 
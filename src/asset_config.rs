@@ -1,6 +1,16 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
 
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use uuid::Uuid;
 
 /// Asset paths configuration.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -9,6 +19,316 @@ pub struct AssetConfig {
     pub target_directory_path: PathBuf,
     /// Internal asset storage (should be persistent between pack runs to avoid re-running filter every time).
     pub internal_directory_path: PathBuf,
-    /// Directory to get asset sources from.
+    /// Directory to get asset sources from. Relative path is resolved against current working directory once at start of pack run, source file paths from manifest are always resolved against this directory.
     pub source_directory_path: PathBuf,
+    /// Additional directories to search for source files of `File` assets (in order) if file is not found in source directory.
+    #[serde(default)]
+    pub source_directory_paths: Vec<PathBuf>,
+    /// Allow absolute source file paths in manifest. If not set, such paths are rejected with `AssetPathError`.
+    #[serde(default)]
+    pub allow_absolute_sources: bool,
+    /// Enabled build features. Assets with required features not enabled are skipped.
+    #[serde(default)]
+    pub enabled_features: HashSet<String>,
+    /// Additional named directories to copy public assets to (besides target directory).
+    #[serde(default)]
+    pub targets: Vec<NamedTarget>,
+    /// Strategy to name asset files in internal and target directories.
+    #[serde(default)]
+    pub naming: NamingStrategy,
+    /// Format of content hash in file names with `ContentHash` naming strategy. Can be overridden by asset.
+    #[serde(default)]
+    pub hash_format: HashFormat,
+    /// Modification time (seconds since Unix epoch) of all entries in archives written by crate, so archives of same files are identical.
+    #[serde(default)]
+    pub archive_timestamp: u64,
+    /// Maximum nesting depth of filtered assets (public asset has depth 1, its inputs have depth 2 and so on). Deeper assets are rejected with `DependencyTooDeep`.
+    #[serde(default = "default_max_dependency_depth")]
+    pub max_dependency_depth: usize,
+    /// Prune cache manifest and asset directories (see `AssetCacheManifestV1::prune`) after every successful processing of public assets.
+    #[serde(default)]
+    pub auto_prune: bool,
+    /// Hash source files of assets with `Hash` change detection on every run. If not set, source files with unchanged size and modification time are not read.
+    #[serde(default)]
+    pub always_hash_sources: bool,
+    /// Template of asset file names (`output_base_path` is prepended to it). Placeholders are `{name}` (asset name), `{hash}` (version suffix: random UUID with `Uuid` naming strategy, encoded content hash with `ContentHash` naming strategy), `{uuid}` (same as `{hash}`) and `{ext}` (asset extension with leading dot, empty if asset has no extension). Paths rendered from template are checked like other asset paths.
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+    /// Algorithm of Subresource Integrity strings computed for public asset files (not computed if not set).
+    #[serde(default)]
+    pub integrity: Option<IntegrityAlgorithm>,
+    /// Algorithm of content hashes of asset files stored in cache manifest. Hashes made by other algorithm are not compared, assets with such hashes are rebuilt.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// How public asset files are placed in target directories. Files of assets with banner are always copied.
+    #[serde(default)]
+    pub publish_mode: PublishMode,
+    /// Compressions of precompressed variants written next to public asset files in target directories (for example, `app.js.gz` for `app.js`).
+    #[serde(default)]
+    pub precompress: Vec<Compression>,
+    /// Copy modification time and permissions of source files to internal files of `File` and `Glob` assets and to target files of `File` assets. Permissions are then overridden by asset `mode` if it is set.
+    #[serde(default)]
+    pub preserve_metadata: bool,
+    /// Replace `${VAR}` in string and string list filter options with values of environment variables right before filter is run (see `expand_env_vars`). Filtered assets are rebuilt if expanded options are changed.
+    #[serde(default)]
+    pub expand_env_vars: bool,
+}
+
+/// Default template of asset file names, see `AssetConfig::filename_template`.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{name}-{hash}{ext}";
+
+fn default_filename_template() -> String {
+    DEFAULT_FILENAME_TEMPLATE.to_string()
+}
+
+fn default_max_dependency_depth() -> usize {
+    64
+}
+
+/// Strategy to generate version suffix of asset file names.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NamingStrategy {
+    /// Random UUID, new for every rebuild.
+    #[default]
+    Uuid,
+    /// UUID made from content hash, same for same file content.
+    ContentHash,
+}
+
+/// How public asset files are placed in target directories.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PublishMode {
+    /// Copy internal file.
+    #[default]
+    Copy,
+    /// Create symbolic link to internal file (absolute path). Internal directory must be kept while target directory is used.
+    Symlink,
+    /// Create hard link to internal file (internal and target directories must be on same file system).
+    Hardlink,
+}
+
+/// Compression of precompressed public asset variants.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    Gzip,
+    Brotli,
+}
+
+impl Compression {
+    /// Return content encoding name as in `Accept-Encoding` header.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Brotli => "br",
+        }
+    }
+
+    /// Return extension appended to public asset file path to get variant file path.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Brotli => "br",
+        }
+    }
+}
+
+/// Encoding of content hash in file names.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashEncoding {
+    /// UUID made from first 16 bytes of hash.
+    #[default]
+    Uuid,
+    /// Lowercase hexadecimal digits.
+    Hex,
+    /// URL-safe base64 without padding.
+    Base64Url,
+}
+
+/// Hash algorithm of Subresource Integrity strings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl IntegrityAlgorithm {
+    /// Return algorithm name used as prefix of integrity string.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegrityAlgorithm::Sha256 => "sha256",
+            IntegrityAlgorithm::Sha384 => "sha384",
+            IntegrityAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Return Subresource Integrity string (for example, `sha384-BASE64`) for given data parts.
+    pub fn integrity(&self, parts: &[&[u8]]) -> String {
+        let reader = parts
+            .iter()
+            .fold(Box::new(io::empty()) as Box<dyn Read>, |reader, part| {
+                Box::new(reader.chain(*part))
+            });
+        self.integrity_of_reader(reader)
+            .expect("reading from memory does not fail")
+    }
+
+    /// Return Subresource Integrity string (for example, `sha384-BASE64`) for content read from reader in chunks.
+    pub fn integrity_of_reader<R>(&self, mut reader: R) -> io::Result<String>
+    where
+        R: Read,
+    {
+        fn digest<D, R>(reader: &mut R) -> io::Result<Vec<u8>>
+        where
+            D: Digest + Write,
+            R: Read,
+        {
+            let mut hasher = D::new();
+            io::copy(reader, &mut hasher)?;
+            Ok(hasher.finalize().to_vec())
+        }
+
+        let hash = match self {
+            IntegrityAlgorithm::Sha256 => digest::<Sha256, R>(&mut reader)?,
+            IntegrityAlgorithm::Sha384 => digest::<Sha384, R>(&mut reader)?,
+            IntegrityAlgorithm::Sha512 => digest::<Sha512, R>(&mut reader)?,
+        };
+        Ok(format!("{}-{}", self.name(), BASE64_STANDARD.encode(hash)))
+    }
+}
+
+/// Algorithm of asset content hashes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Return algorithm name used as prefix of serialized hash.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Return algorithm with given name (see `name`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blake3" => Some(HashAlgorithm::Blake3),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Return hash length in bytes.
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Blake3 => blake3::OUT_LEN,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+}
+
+/// Format of content hash in file names.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HashFormat {
+    /// Number of characters to keep from encoded hash (full encoded hash if not set). Should not be zero and should not be set for `Uuid` encoding (see `is_valid`).
+    #[serde(default)]
+    pub length: Option<usize>,
+    #[serde(default)]
+    pub encoding: HashEncoding,
+}
+
+impl HashFormat {
+    /// Return `true` if format can be used: length is not zero and is not set for `Uuid` encoding.
+    pub fn is_valid(&self) -> bool {
+        !matches!(
+            (self.length, self.encoding),
+            (Some(0), _) | (Some(_), HashEncoding::Uuid)
+        )
+    }
+
+    /// Encode content hash according to format.
+    pub fn encode(&self, hash: &[u8]) -> String {
+        let mut encoded = match self.encoding {
+            HashEncoding::Uuid => {
+                let mut uuid_bytes = [0; 16];
+                uuid_bytes.copy_from_slice(&hash[..16]);
+                return Uuid::from_bytes(uuid_bytes).to_string();
+            }
+            HashEncoding::Hex => hash.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            HashEncoding::Base64Url => BASE64_URL_SAFE_NO_PAD.encode(hash),
+        };
+        if let Some(length) = self.length {
+            encoded.truncate(length);
+        }
+        encoded
+    }
+}
+
+/// Additional target directory (for example, fallback CDN root or locale-specific root).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NamedTarget {
+    /// Target name, used to get public path of asset for this target.
+    pub name: String,
+    /// Directory to copy public assets to.
+    pub directory_path: PathBuf,
+}
+
+impl AssetConfig {
+    /// Create config with given directories and default options.
+    pub fn new(
+        target_directory_path: PathBuf,
+        internal_directory_path: PathBuf,
+        source_directory_path: PathBuf,
+    ) -> Self {
+        AssetConfig {
+            target_directory_path,
+            internal_directory_path,
+            source_directory_path,
+            source_directory_paths: Vec::new(),
+            allow_absolute_sources: false,
+            enabled_features: HashSet::new(),
+            targets: Vec::new(),
+            naming: NamingStrategy::default(),
+            hash_format: HashFormat::default(),
+            archive_timestamp: 0,
+            max_dependency_depth: default_max_dependency_depth(),
+            auto_prune: false,
+            always_hash_sources: false,
+            filename_template: default_filename_template(),
+            integrity: None,
+            hash_algorithm: HashAlgorithm::default(),
+            publish_mode: PublishMode::default(),
+            precompress: Vec::new(),
+            preserve_metadata: false,
+            expand_env_vars: false,
+        }
+    }
+
+    /// Return all directories to copy public assets to: target directory first, then directories of named targets.
+    pub fn target_directory_paths(&self) -> Vec<&Path> {
+        let mut result = vec![self.target_directory_path.as_path()];
+        result.extend(
+            self.targets
+                .iter()
+                .map(|target| target.directory_path.as_path()),
+        );
+        result
+    }
+
+    /// Return directory of target with given name, or target directory if name is `None`. Return `None` if there is no target with given name.
+    pub fn get_target_directory_path(&self, target_name: Option<&str>) -> Option<&Path> {
+        match target_name {
+            None => Some(&self.target_directory_path),
+            Some(target_name) => self
+                .targets
+                .iter()
+                .find(|target| target.name == target_name)
+                .map(|target| target.directory_path.as_path()),
+        }
+    }
 }
@@ -1,22 +1,95 @@
 use std::{
+    any::Any,
+    borrow::Cow,
     collections::HashMap,
+    env,
+    fs::{create_dir_all, remove_dir_all, File},
+    io::{self, BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use log::debug;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::assets::{AssetError, AssetFilterError};
+use crate::{
+    asset_config::AssetConfig,
+    assets::{
+        is_safe_relative_path, serialize_sorted_map, AssetError, AssetErrorType, AssetFilterError,
+    },
+};
 
 /// Options passed to asset filter.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum AssetFilterOption {
     Flag,
     Bool(bool),
     String(String),
     StringList(Vec<String>),
+    Integer(i64),
+    Float(f64),
+    /// Nested options.
+    Map(#[serde(serialize_with = "serialize_sorted_map")] HashMap<String, AssetFilterOption>),
+    /// Binary data, written as base64 string in manifest.
+    Bytes(#[serde(with = "base64_bytes")] Vec<u8>),
+    /// Relative path, should be checked with `get_safe_path`.
+    Path(PathBuf),
+}
+
+/// Serialize bytes as base64 string.
+pub(crate) mod base64_bytes {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(&encoded)
+            .map_err(|err| D::Error::custom(format!("invalid base64: {}", err)))
+    }
+}
+
+// Floats are compared by bits, so every option is equal to itself (including NaN) and change detection stays consistent.
+impl PartialEq for AssetFilterOption {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AssetFilterOption::Flag, AssetFilterOption::Flag) => true,
+            (AssetFilterOption::Bool(value1), AssetFilterOption::Bool(value2)) => value1 == value2,
+            (AssetFilterOption::String(value1), AssetFilterOption::String(value2)) => {
+                value1 == value2
+            }
+            (AssetFilterOption::StringList(value1), AssetFilterOption::StringList(value2)) => {
+                value1 == value2
+            }
+            (AssetFilterOption::Integer(value1), AssetFilterOption::Integer(value2)) => {
+                value1 == value2
+            }
+            (AssetFilterOption::Float(value1), AssetFilterOption::Float(value2)) => {
+                value1.to_bits() == value2.to_bits()
+            }
+            (AssetFilterOption::Map(value1), AssetFilterOption::Map(value2)) => value1 == value2,
+            (AssetFilterOption::Bytes(value1), AssetFilterOption::Bytes(value2)) => {
+                value1 == value2
+            }
+            (AssetFilterOption::Path(value1), AssetFilterOption::Path(value2)) => value1 == value2,
+            _ => false,
+        }
+    }
 }
 
+impl Eq for AssetFilterOption {}
+
 /// Return `Some(true)` if option is set and is flag, `Some(false)` if option is not set, `None` if option has other type.
 pub fn option_is_flag(option: Option<AssetFilterOption>) -> Option<bool> {
     match option {
@@ -50,28 +123,430 @@ pub fn get_string_list(option: AssetFilterOption) -> Option<Vec<String>> {
     }
 }
 
+/// Return `Some(x)` if option is integer `x`, `None` if options has other type.
+pub fn get_integer(option: AssetFilterOption) -> Option<i64> {
+    match option {
+        AssetFilterOption::Integer(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Return `Some(x)` if option is float `x`, `None` if options has other type.
+pub fn get_float(option: AssetFilterOption) -> Option<f64> {
+    match option {
+        AssetFilterOption::Float(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Return `Some(x)` if option is map of nested options `x`, `None` if options has other type.
+pub fn get_map(option: AssetFilterOption) -> Option<HashMap<String, AssetFilterOption>> {
+    match option {
+        AssetFilterOption::Map(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Return `Some(x)` if option is binary data `x`, `None` if options has other type.
+pub fn get_bytes(option: AssetFilterOption) -> Option<Vec<u8>> {
+    match option {
+        AssetFilterOption::Bytes(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Return `Ok(Some(x))` with option path `x` joined to base directory if option is path, `Ok(None)` if option has other type. Return `AssetPathError` if path is absolute or points outside of base directory (same check as for asset output paths).
+pub fn get_safe_path<E>(
+    option: AssetFilterOption,
+    base: &Path,
+) -> Result<Option<PathBuf>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    match option {
+        AssetFilterOption::Path(path) if is_safe_relative_path(&path) => Ok(Some(base.join(path))),
+        AssetFilterOption::Path(path) => Err(AssetError::new(AssetErrorType::AssetPathError(path))),
+        _ => Ok(None),
+    }
+}
+
+/// Return value of required option using getter (like `get_string`). Return `MissingFilterOption` error if option is not set or has other type.
+pub fn require_option<T, E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+    getter: fn(AssetFilterOption) -> Option<T>,
+) -> Result<T, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    options
+        .get(key)
+        .cloned()
+        .and_then(getter)
+        .ok_or_else(|| AssetError::new(AssetErrorType::MissingFilterOption(key.to_string())))
+}
+
+/// Return value of required boolean option, see `require_option`.
+pub fn require_bool<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<bool, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_bool)
+}
+
+/// Return value of required string option, see `require_option`.
+pub fn require_string<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<String, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_string)
+}
+
+/// Return value of required string list option, see `require_option`.
+pub fn require_string_list<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<Vec<String>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_string_list)
+}
+
+/// Return value of required integer option, see `require_option`.
+pub fn require_integer<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<i64, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_integer)
+}
+
+/// Return value of required float option, see `require_option`.
+pub fn require_float<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<f64, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_float)
+}
+
+/// Return value of required map option, see `require_option`.
+pub fn require_map<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<HashMap<String, AssetFilterOption>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_map)
+}
+
+/// Return value of required binary data option, see `require_option`.
+pub fn require_bytes<E>(
+    options: &HashMap<String, AssetFilterOption>,
+    key: &str,
+) -> Result<Vec<u8>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    require_option(options, key, get_bytes)
+}
+
+/// Return options with `${VAR}` in string and string list options (including nested maps) replaced by values of environment variables. Return `UndefinedEnvVar` if variable is not set. Unterminated `${` is kept as is.
+pub fn expand_env_vars<E>(
+    options: &HashMap<String, AssetFilterOption>,
+) -> Result<HashMap<String, AssetFilterOption>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    options
+        .iter()
+        .map(|(key, option)| Ok((key.clone(), expand_option_env_vars(option)?)))
+        .collect()
+}
+
+/// Return options passed to filter: options from manifest, with environment variables expanded if `expand_env_vars` is set in config.
+pub(crate) fn filter_options<'a, E>(
+    options: &'a HashMap<String, AssetFilterOption>,
+    config: &AssetConfig,
+) -> Result<Cow<'a, HashMap<String, AssetFilterOption>>, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    if config.expand_env_vars {
+        Ok(Cow::Owned(expand_env_vars(options)?))
+    } else {
+        Ok(Cow::Borrowed(options))
+    }
+}
+
+fn expand_option_env_vars<E>(option: &AssetFilterOption) -> Result<AssetFilterOption, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    Ok(match option {
+        AssetFilterOption::String(value) => {
+            AssetFilterOption::String(expand_string_env_vars(value)?)
+        }
+        AssetFilterOption::StringList(values) => AssetFilterOption::StringList(
+            values
+                .iter()
+                .map(|value| expand_string_env_vars(value))
+                .collect::<Result<_, _>>()?,
+        ),
+        AssetFilterOption::Map(options) => AssetFilterOption::Map(expand_env_vars(options)?),
+        option => option.clone(),
+    })
+}
+
+fn expand_string_env_vars<E>(value: &str) -> Result<String, AssetError<E>>
+where
+    E: AssetFilterError,
+{
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start + 2..].find('}') {
+            Some(end) => start + 2 + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+        let name_value = env::var(name)
+            .map_err(|_| AssetError::new(AssetErrorType::UndefinedEnvVar(name.to_string())))?;
+        result.push_str(&rest[..start]);
+        result.push_str(&name_value);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
 /// Trait for filters that process assets.
 pub trait AssetFilter<E>
 where
     E: AssetFilterError,
 {
-    /// Process asset: take input files and write output to output file.
+    /// Process asset: take input files and write output to output file. Filters working with streams can implement `AssetFilterStream` instead and be registered wrapped in `StreamFilter`.
     fn process_asset_file(
         &self,
         input_file_paths: &[PathBuf],
         output_file_path: &Path,
         options: &HashMap<String, AssetFilterOption>,
     ) -> Result<(), AssetError<E>>;
+
+    /// Process asset: read inputs and write output. Default implementation writes inputs to temporary files, calls `process_asset_file` and copies output file.
+    fn process_asset_stream(
+        &self,
+        inputs: &mut [Box<dyn Read>],
+        output: &mut dyn Write,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        let temp_directory_path = env::temp_dir().join(format!("asset-filter-{}", Uuid::new_v4()));
+        create_dir_all(&temp_directory_path)?;
+        let result = (|| {
+            let mut input_file_paths: Vec<PathBuf> = Vec::with_capacity(inputs.len());
+            for (index, input) in inputs.iter_mut().enumerate() {
+                let input_file_path = temp_directory_path.join(format!("input-{}", index));
+                io::copy(input, &mut File::create(&input_file_path)?)?;
+                input_file_paths.push(input_file_path);
+            }
+            let output_file_path = temp_directory_path.join("output");
+            self.process_asset_file(&input_file_paths, &output_file_path, options)?;
+            io::copy(&mut File::open(&output_file_path)?, output)?;
+            Ok(())
+        })();
+        remove_dir_all(&temp_directory_path)?;
+        result
+    }
+
+    /// Same as `process_asset_file`, but with access to filter context. Registry calls this method, default implementation ignores context.
+    fn process_asset_file_with_context(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+        _context: &FilterContext,
+    ) -> Result<(), AssetError<E>> {
+        self.process_asset_file(input_file_paths, output_file_path, options)
+    }
+
+    /// Check output file written by filter. If error is returned, output file is removed and asset is not cached.
+    fn validate_output(
+        &self,
+        _output_file_path: &Path,
+        _options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        Ok(())
+    }
+
+    /// Return `true` if filter can process empty list of input files. Otherwise assets with no inputs fail with `NoFilterInputs` error.
+    fn accepts_empty_inputs(&self) -> bool {
+        false
+    }
+
+    /// Return maximum number of instances of this filter that may run simultaneously, `None` if not limited.
+    ///
+    /// Synchronous pack runs filters one at a time, so this limit is not enforced until parallel scheduler is implemented.
+    fn max_concurrency(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Trait for filters that process assets as streams, without access to file paths. Register such filter wrapped in `StreamFilter`.
+pub trait AssetFilterStream<E>
+where
+    E: AssetFilterError,
+{
+    /// Process asset: read inputs and write output.
+    fn process_asset_stream(
+        &self,
+        inputs: &mut [Box<dyn Read>],
+        output: &mut dyn Write,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>>;
+
+    /// Return `true` if filter can process empty list of inputs, see `AssetFilter::accepts_empty_inputs`.
+    fn accepts_empty_inputs(&self) -> bool {
+        false
+    }
+}
+
+/// Adapter implementing `AssetFilter` for stream filter: input files are opened and output file is created for `AssetFilterStream::process_asset_stream`.
+pub struct StreamFilter<F>(pub F);
+
+impl<E, F> AssetFilter<E> for StreamFilter<F>
+where
+    E: AssetFilterError,
+    F: AssetFilterStream<E>,
+{
+    fn process_asset_file(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        let mut inputs: Vec<Box<dyn Read>> = Vec::with_capacity(input_file_paths.len());
+        for input_file_path in input_file_paths {
+            inputs.push(Box::new(BufReader::new(File::open(input_file_path)?)));
+        }
+        if let Some(output_file_path_parent) = output_file_path.parent() {
+            create_dir_all(output_file_path_parent)?;
+        }
+        let mut output = BufWriter::new(File::create(output_file_path)?);
+        self.0
+            .process_asset_stream(&mut inputs, &mut output, options)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    fn process_asset_stream(
+        &self,
+        inputs: &mut [Box<dyn Read>],
+        output: &mut dyn Write,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        self.0.process_asset_stream(inputs, output, options)
+    }
+
+    fn accepts_empty_inputs(&self) -> bool {
+        self.0.accepts_empty_inputs()
+    }
+}
+
+/// Context passed by filter registry to filters.
+#[derive(Clone, Default)]
+pub struct FilterContext {
+    /// Shared state set in filter registry (for example, HTTP client or compiler cache).
+    pub shared: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl FilterContext {
+    /// Return shared state if it is set and has type `T`.
+    pub fn shared<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.shared.as_ref()?.downcast_ref()
+    }
 }
 
 pub struct AssetFilterRegistry<E> {
     filters: HashMap<String, Box<dyn AssetFilter<E>>>,
+    context: FilterContext,
 }
 
 impl<E> AssetFilterRegistry<E> {
     /// Create asset filter registry from HashMap.
     pub fn new(filters: HashMap<String, Box<dyn AssetFilter<E>>>) -> AssetFilterRegistry<E> {
-        AssetFilterRegistry { filters }
+        AssetFilterRegistry {
+            filters,
+            context: FilterContext::default(),
+        }
+    }
+
+    /// Register filter with given name (replacing filter registered with same name).
+    pub fn with_filter(mut self, filter_name: &str, filter: Box<dyn AssetFilter<E>>) -> Self {
+        self.filters.insert(filter_name.to_string(), filter);
+        self
+    }
+
+    /// Set shared state passed to filters in filter context.
+    pub fn with_shared_context(mut self, shared: Arc<dyn Any + Send + Sync>) -> Self {
+        self.context.shared = Some(shared);
+        self
+    }
+
+    /// Return `true` if filter with name filter_name is registered.
+    pub fn has_filter(&self, filter_name: &str) -> bool {
+        self.filters.contains_key(filter_name)
+    }
+
+    /// Return `Some(true)` if filter with name filter_name accepts empty list of inputs, `None` if filter is not registered.
+    pub fn accepts_empty_inputs(&self, filter_name: &str) -> Option<bool>
+    where
+        E: AssetFilterError,
+    {
+        self.filters
+            .get(filter_name)
+            .map(|filter| filter.accepts_empty_inputs())
+    }
+
+    /// Return maximum number of simultaneously running instances of filter with name filter_name, `None` if filter is not registered or not limited.
+    ///
+    /// Limit is not enforced by synchronous pack yet, see `AssetFilter::max_concurrency`.
+    pub fn max_concurrency(&self, filter_name: &str) -> Option<usize>
+    where
+        E: AssetFilterError,
+    {
+        self.filters
+            .get(filter_name)
+            .and_then(|filter| filter.max_concurrency())
+    }
+
+    /// Check output file written by filter with name filter_name.
+    pub fn validate_output(
+        &self,
+        filter_name: &str,
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Option<Result<(), AssetError<E>>>
+    where
+        E: AssetFilterError,
+    {
+        self.filters
+            .get(filter_name)
+            .map(|filter| filter.validate_output(output_file_path, options))
     }
 
     /// Process assets by filter with name filter_name.
@@ -90,8 +565,13 @@ impl<E> AssetFilterRegistry<E> {
             input_file_paths, output_file_path, filter_name
         );
 
-        self.filters
-            .get(&filter_name)
-            .map(|filter| filter.process_asset_file(input_file_paths, output_file_path, options))
+        self.filters.get(&filter_name).map(|filter| {
+            filter.process_asset_file_with_context(
+                input_file_paths,
+                output_file_path,
+                options,
+                &self.context,
+            )
+        })
     }
 }
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, remove_file},
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+use async_trait::async_trait;
+use log::debug;
+use uuid::Uuid;
+
+use crate::{
+    asset_cache::{check_public_asset_names, AssetCacheEntry, AssetCacheManifestV1},
+    asset_config::AssetConfig,
+    asset_filter::{filter_options, AssetFilterOption, AssetFilterRegistry},
+    asset_pack::{PackContext, PackReport},
+    assets::{
+        AssetError, AssetErrorType, AssetFilterError, AssetManifest, AssetResult, AssetSource,
+    },
+};
+
+/// Asset filter processing files asynchronously (for example, waiting for external process or network service).
+#[async_trait]
+pub trait AssetFilterAsync<E>: Send + Sync
+where
+    E: AssetFilterError,
+{
+    /// Process asset: take input files and write output to output file.
+    async fn process_asset_file(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>>;
+
+    /// Return `true` if filter can process empty list of input files. Otherwise assets with no inputs fail with `NoFilterInputs` error.
+    fn accepts_empty_inputs(&self) -> bool {
+        false
+    }
+
+    /// Return maximum number of instances of this filter that may run simultaneously (for example, when registry is shared by pack runs awaited together), `None` if not limited.
+    fn max_concurrency(&self) -> Option<usize> {
+        None
+    }
+}
+
+pub struct AssetFilterRegistryAsync<E> {
+    filters: HashMap<String, Box<dyn AssetFilterAsync<E>>>,
+    /// Semaphores of filters with limited concurrency.
+    limits: HashMap<String, FilterSemaphore>,
+}
+
+/// Counting semaphore limiting number of simultaneously running instances of filter.
+struct FilterSemaphore {
+    state: Mutex<SemaphoreState>,
+}
+
+struct SemaphoreState {
+    available: usize,
+    waiters: Vec<Waker>,
+}
+
+impl FilterSemaphore {
+    fn new(permits: usize) -> Self {
+        FilterSemaphore {
+            state: Mutex::new(SemaphoreState {
+                available: permits,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    fn acquire(&self) -> SemaphoreAcquire<'_> {
+        SemaphoreAcquire { semaphore: self }
+    }
+}
+
+struct SemaphoreAcquire<'a> {
+    semaphore: &'a FilterSemaphore,
+}
+
+impl<'a> Future for SemaphoreAcquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.semaphore.state.lock().unwrap();
+        if state.available > 0 {
+            state.available -= 1;
+            Poll::Ready(SemaphorePermit {
+                semaphore: self.semaphore,
+            })
+        } else {
+            state.waiters.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Permit of semaphore, released when dropped.
+struct SemaphorePermit<'a> {
+    semaphore: &'a FilterSemaphore,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.semaphore.state.lock().unwrap();
+            state.available += 1;
+            std::mem::take(&mut state.waiters)
+        };
+        // All waiters are woken, as some of them may be already dropped.
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl<E> AssetFilterRegistryAsync<E>
+where
+    E: AssetFilterError,
+{
+    /// Create asynchronous asset filter registry from HashMap.
+    pub fn new(filters: HashMap<String, Box<dyn AssetFilterAsync<E>>>) -> Self {
+        let limits = filters
+            .iter()
+            .filter_map(|(filter_name, filter)| {
+                filter
+                    .max_concurrency()
+                    .map(|permits| (filter_name.clone(), FilterSemaphore::new(permits.max(1))))
+            })
+            .collect();
+        AssetFilterRegistryAsync { filters, limits }
+    }
+
+    /// Return `true` if filter with name filter_name is registered.
+    pub fn has_filter(&self, filter_name: &str) -> bool {
+        self.filters.contains_key(filter_name)
+    }
+
+    /// Return `Some(true)` if filter with name filter_name accepts empty list of inputs, `None` if filter is not registered.
+    pub fn accepts_empty_inputs(&self, filter_name: &str) -> Option<bool> {
+        self.filters
+            .get(filter_name)
+            .map(|filter| filter.accepts_empty_inputs())
+    }
+
+    /// Return maximum number of simultaneously running instances of filter with name filter_name, `None` if filter is not registered or not limited.
+    pub fn max_concurrency(&self, filter_name: &str) -> Option<usize> {
+        self.filters
+            .get(filter_name)
+            .and_then(|filter| filter.max_concurrency())
+    }
+
+    /// Process assets by filter with name filter_name. If filter has limited concurrency, wait until number of its running instances is below limit.
+    pub async fn process_asset_file(
+        &self,
+        filter_name: &str,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Option<Result<(), AssetError<E>>> {
+        debug!(
+            "Processing files {:?} to output file {:?} by asynchronous filter {}",
+            input_file_paths, output_file_path, filter_name
+        );
+
+        let filter = self.filters.get(filter_name)?;
+        let _permit = match self.limits.get(filter_name) {
+            Some(semaphore) => Some(semaphore.acquire().await),
+            None => None,
+        };
+        Some(
+            filter
+                .process_asset_file(input_file_paths, output_file_path, options)
+                .await,
+        )
+    }
+}
+
+impl AssetManifest {
+    /// Check that all filters used in manifest are registered in asynchronous filter registry.
+    pub fn check_filters_async<E>(
+        &self,
+        filter_registry: &AssetFilterRegistryAsync<E>,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        self.check_filter_names(&|filter_name| filter_registry.has_filter(filter_name))
+    }
+}
+
+impl AssetCacheManifestV1 {
+    /// Same as `process`, but filtered assets are built by asynchronous filters. Assets are checked by the same logic as in `process`, assets to rebuild are processed sequentially, inputs first.
+    ///
+    /// Filter outputs are prepared before cache entries are rebuilt, so filter registry in context is not used.
+    pub async fn process_async<E>(
+        &mut self,
+        name: String,
+        filter_registry: &AssetFilterRegistryAsync<E>,
+        context: &mut PackContext<'_, E>,
+    ) -> AssetResult<(AssetCacheEntry, bool), E>
+    where
+        E: AssetFilterError,
+    {
+        context.async_filters = true;
+        let plan = self.plan_public_asset_names(std::slice::from_ref(&name), context)?;
+        for rebuild in &plan.rebuilds {
+            if context.is_resolved(&rebuild.name) {
+                continue;
+            }
+            // Asset planned only because of rebuilt inputs is resolved later without running filter if their content is unchanged.
+            if rebuild.tentative && !self.tentative_rebuild_needed(&rebuild.name, context)? {
+                continue;
+            }
+            self.prepare_filtered_output(&rebuild.name, filter_registry, context)
+                .await?;
+            let result = self.process(rebuild.name.clone(), context);
+            // Prepared output is left if asset was not rebuilt after all.
+            if let Some(prepared_full_path) = context.prepared_outputs.remove(&rebuild.name) {
+                remove_file(prepared_full_path)?;
+            }
+            result?;
+        }
+        self.process(name, context)
+    }
+
+    /// Resolve inputs of filtered asset planned to be rebuilt only because its inputs are, and return `true` if it should be rebuilt (content of inputs is changed or internal file is missing).
+    fn tentative_rebuild_needed<E>(
+        &mut self,
+        name: &str,
+        context: &mut PackContext<'_, E>,
+    ) -> AssetResult<bool, E>
+    where
+        E: AssetFilterError,
+    {
+        let filtered = match context.manifest.assets.get(name).map(|data| &data.source) {
+            Some(AssetSource::Filtered(filtered)) => filtered,
+            _ => return Ok(true),
+        };
+
+        let mut has_updated_inputs = false;
+        let mut input_entries: Vec<AssetCacheEntry> =
+            Vec::with_capacity(filtered.input_names.len());
+        for input_name in &filtered.input_names {
+            let (input_entry, changed) = self.process(input_name.clone(), context)?;
+            has_updated_inputs |= changed;
+            input_entries.push(input_entry);
+        }
+
+        let cache_entry = match self.map.get(name) {
+            Some(cache_entry) => cache_entry,
+            None => return Ok(true),
+        };
+        if !context
+            .config
+            .internal_directory_path
+            .join(&cache_entry.path)
+            .exists()
+        {
+            return Ok(true);
+        }
+        Ok(cache_entry
+            .inputs_rebuild_reason(filtered, &input_entries, has_updated_inputs, context.config)?
+            .is_some())
+    }
+
+    /// Run asynchronous filter of filtered asset and record its output in context, so `AssetCacheEntry::create` uses it instead of running filter.
+    async fn prepare_filtered_output<E>(
+        &mut self,
+        name: &str,
+        filter_registry: &AssetFilterRegistryAsync<E>,
+        context: &mut PackContext<'_, E>,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let filtered = match context.manifest.assets.get(name).map(|data| &data.source) {
+            Some(AssetSource::Filtered(filtered)) => filtered,
+            _ => return Ok(()),
+        };
+
+        let mut input_full_paths: Vec<PathBuf> = Vec::with_capacity(filtered.input_names.len());
+        for input_name in &filtered.input_names {
+            let (input_entry, _) = self.process(input_name.clone(), context)?;
+            input_full_paths.extend(input_entry.input_full_paths(context.config));
+        }
+
+        if input_full_paths.is_empty()
+            && filter_registry.accepts_empty_inputs(&filtered.filter_name) == Some(false)
+        {
+            return Err(AssetError::new(AssetErrorType::NoFilterInputs(
+                name.to_string(),
+            )));
+        }
+
+        create_dir_all(&context.config.internal_directory_path)?;
+        let prepared_full_path = context
+            .config
+            .internal_directory_path
+            .join(format!(".prepared-{}", Uuid::new_v4()));
+        let options = filter_options(&filtered.options, context.config)?;
+        let result = filter_registry
+            .process_asset_file(
+                &filtered.filter_name,
+                &input_full_paths,
+                &prepared_full_path,
+                &options,
+            )
+            .await
+            .ok_or_else(|| {
+                AssetError::new(AssetErrorType::AssetFilterNotFoundError(
+                    filtered.filter_name.clone(),
+                ))
+            })?;
+        if let Err(err) = result {
+            if prepared_full_path.exists() {
+                remove_file(&prepared_full_path)?;
+            }
+            return Err(err);
+        }
+        context
+            .prepared_outputs
+            .insert(name.to_string(), prepared_full_path);
+        Ok(())
+    }
+
+    /// Same as `process_public_assets`, but filtered assets are built by asynchronous filters, see `process_async`.
+    pub async fn process_public_assets_async<E>(
+        &mut self,
+        config: &AssetConfig,
+        manifest: &AssetManifest,
+        filter_registry: &AssetFilterRegistryAsync<E>,
+    ) -> AssetResult<PackReport, E>
+    where
+        E: AssetFilterError,
+    {
+        let empty_registry = AssetFilterRegistry::new(HashMap::new());
+        let mut context = PackContext::new(config, manifest, &empty_registry);
+        self.process_public_asset_names_async(
+            &manifest.public_assets,
+            filter_registry,
+            &mut context,
+        )
+        .await?;
+        Ok(context.report)
+    }
+
+    /// Same as `process_public_asset_names` (copying all given assets), but filtered assets are built by asynchronous filters, see `process_async`.
+    pub async fn process_public_asset_names_async<E>(
+        &mut self,
+        names: &[String],
+        filter_registry: &AssetFilterRegistryAsync<E>,
+        context: &mut PackContext<'_, E>,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        check_public_asset_names(names, context)?;
+        for asset_name in names {
+            let enabled = context
+                .manifest
+                .assets
+                .get(asset_name)
+                .is_none_or(|data| data.is_enabled(&context.config.enabled_features));
+            if !enabled {
+                continue;
+            }
+
+            match self
+                .process_async(asset_name.clone(), filter_registry, context)
+                .await
+            {
+                Ok((_, changed)) => {
+                    if changed {
+                        self.checkpoint(context)?;
+                    }
+                }
+                Err(err) => {
+                    self.checkpoint(context)?;
+                    return Err(err);
+                }
+            }
+        }
+
+        // All assets are already resolved, so they are only copied to target directory.
+        self.process_public_asset_names(names, &|_| true, context)
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions, TryLockError},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_cache::AssetCacheEntry,
+    asset_config::AssetConfig,
+    asset_filter::AssetFilterRegistry,
+    assets::{AssetData, AssetError, AssetErrorType, AssetFilterError, AssetManifest, AssetResult},
+    clock::{Clock, SystemClock},
+    observer::PackObserver,
+};
+
+/// Name of lock file in internal directory, locked during pack run.
+pub(crate) const LOCK_FILE_NAME: &str = ".pack.lock";
+
+/// Advisory lock of internal directory, so pack runs using same internal directory do not run simultaneously. Lock is released when value is dropped.
+#[derive(Debug)]
+pub struct PackLock {
+    _file: File,
+}
+
+impl PackLock {
+    /// Lock internal directory from config (directory is created if needed). Return `LockError` if it is locked by another pack run.
+    pub fn acquire<E>(config: &AssetConfig) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        fs::create_dir_all(&config.internal_directory_path)?;
+        let lock_path = config.internal_directory_path.join(LOCK_FILE_NAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(PackLock { _file: file }),
+            Err(TryLockError::WouldBlock) => {
+                Err(AssetError::new(AssetErrorType::LockError(lock_path)))
+            }
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        }
+    }
+}
+
+/// Summary of single pack run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackReport {
+    /// Names of resolved assets in order of resolution. Asset inputs are resolved before asset itself.
+    pub resolution_order: Vec<String>,
+    /// Groups of public assets with identical output content (sorted names, groups sorted by first name). Identical outputs of differently named assets are often manifest mistakes.
+    pub duplicate_outputs: Vec<Vec<String>>,
+    /// Full paths of files removed by pruning (sorted), if `auto_prune` is set in config.
+    pub pruned: Vec<PathBuf>,
+    /// Names of assets rebuilt during this run, in order of resolution.
+    #[serde(default)]
+    pub rebuilt: Vec<String>,
+    /// Names of assets reused from cache during this run, in order of resolution.
+    #[serde(default)]
+    pub skipped: Vec<String>,
+    /// Total size in bytes of files written to internal directory (rebuilt assets) and target directories (published files and their precompressed variants).
+    #[serde(default)]
+    pub bytes_written: u64,
+    /// Names of assets removed from cache manifest (with their files) because they are no longer defined in manifest, sorted.
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+/// Asset rebuilt according to build plan.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRebuild {
+    pub name: String,
+    /// Human-readable reason of rebuild.
+    pub reason: String,
+    /// Filtered asset is planned to be rebuilt only because its inputs are. Filters are not run while planning, so content of rebuilt inputs is not known, and asset is not rebuilt if rebuilt inputs have same content as before.
+    #[serde(default)]
+    pub tentative: bool,
+}
+
+/// Assets that should be rebuilt, computed without building anything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildPlan {
+    /// Assets to rebuild, asset inputs go before asset itself.
+    pub rebuilds: Vec<PlannedRebuild>,
+}
+
+impl BuildPlan {
+    /// Return reason of rebuild if asset should be rebuilt.
+    pub fn reason(&self, name: &str) -> Option<&str> {
+        self.rebuilds
+            .iter()
+            .find(|rebuild| rebuild.name == name)
+            .map(|rebuild| rebuild.reason.as_str())
+    }
+
+    /// Return names of assets to rebuild in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.rebuilds
+            .iter()
+            .map(|rebuild| rebuild.name.as_str())
+            .collect()
+    }
+}
+
+/// State shared by all assets processed during single pack run.
+pub struct PackContext<'a, E> {
+    pub config: &'a AssetConfig,
+    pub manifest: &'a AssetManifest,
+    pub filter_registry: &'a AssetFilterRegistry<E>,
+    pub report: PackReport,
+    /// Canonicalized source directory path, resolved once per run.
+    pub source_root: PathBuf,
+    /// Canonicalized source directory paths in search order (source directory first, then additional source directories), resolved once per run.
+    pub source_roots: Vec<PathBuf>,
+    /// If set, cache manifest is saved to this path after every rebuilt public asset and on failure.
+    pub checkpoint_path: Option<&'a Path>,
+    /// Names of assets rebuilt during this run.
+    pub(crate) rebuilt: HashSet<String>,
+    /// Assets resolved during this run, mapped to `true` if they were rebuilt. Resolved assets are not checked again in same run, new context is created for every run.
+    pub(crate) resolved: HashMap<String, bool>,
+    /// Full paths of target files copied during this run.
+    pub(crate) published: HashSet<PathBuf>,
+    /// Names of assets being processed (each asset uses next one as input), used to detect dependency cycles.
+    pub(crate) visiting: Vec<String>,
+    /// Full paths of filter outputs already written for filtered assets (by asynchronous filters), used instead of running filter from registry.
+    pub(crate) prepared_outputs: HashMap<String, PathBuf>,
+    /// Filtered assets are built by asynchronous filters, so output of every rebuilt filtered asset should be prepared.
+    pub(crate) async_filters: bool,
+    /// Source of current time (system clock by default).
+    pub clock: &'a dyn Clock,
+    /// Callback consulted when cached asset is checked: `Some(true)` forces rebuild, `Some(false)` skips it (unless internal file is missing), `None` leaves decision to change detection.
+    pub should_rebuild: Option<&'a ShouldRebuild>,
+    /// If set, cached assets are rebuilt according to this plan instead of checking them again.
+    pub plan: Option<&'a BuildPlan>,
+    /// Observer notified when assets are processed.
+    pub observer: Option<&'a dyn PackObserver>,
+}
+
+/// Type of callback overriding decision to rebuild cached asset, see `PackContext::should_rebuild`.
+pub type ShouldRebuild = dyn Fn(&AssetCacheEntry, &AssetData) -> Option<bool>;
+
+impl<'a, E> PackContext<'a, E> {
+    /// Create context for new pack run. Source directory paths are canonicalized relative to current working directory.
+    pub fn new(
+        config: &'a AssetConfig,
+        manifest: &'a AssetManifest,
+        filter_registry: &'a AssetFilterRegistry<E>,
+    ) -> Self {
+        let source_roots = canonical_source_roots(config);
+
+        PackContext {
+            config,
+            manifest,
+            filter_registry,
+            report: PackReport::default(),
+            source_root: source_roots[0].clone(),
+            source_roots,
+            checkpoint_path: None,
+            rebuilt: HashSet::new(),
+            resolved: HashMap::new(),
+            published: HashSet::new(),
+            visiting: Vec::new(),
+            prepared_outputs: HashMap::new(),
+            async_filters: false,
+            clock: &SystemClock,
+            should_rebuild: None,
+            plan: None,
+            observer: None,
+        }
+    }
+
+    /// Return `true` if asset was already resolved during this run.
+    pub(crate) fn is_resolved(&self, name: &str) -> bool {
+        self.resolved.contains_key(name)
+    }
+
+    /// Record that asset was resolved. Return `true` if asset was rebuilt during this run.
+    pub(crate) fn record_resolved(&mut self, name: &str, changed: bool) -> bool {
+        if changed {
+            self.rebuilt.insert(name.to_string());
+        }
+        let changed = changed || self.rebuilt.contains(name);
+        if self.resolved.insert(name.to_string(), changed).is_none() {
+            self.report.resolution_order.push(name.to_string());
+        }
+        changed
+    }
+}
+
+impl<'a, E> PackContext<'a, E>
+where
+    E: AssetFilterError,
+{
+    /// Resolve source file path from manifest against source directories (first existing file is used). Absolute paths are rejected unless allowed by config. Return `AssetSourceNotFound` if file is not found.
+    pub fn source_file_path(&self, path: &Path) -> AssetResult<PathBuf, E> {
+        resolve_source_file_path(self.config, &self.source_roots, path)
+    }
+
+    /// Return full path of source directory (path from manifest), searching all source directories like `source_file_path`.
+    pub fn source_directory_path(&self, path: &Path) -> AssetResult<PathBuf, E> {
+        let full_path = self.source_file_path(path)?;
+        if !full_path.is_dir() {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(
+                path.to_path_buf(),
+            )));
+        }
+        Ok(full_path)
+    }
+
+    /// Return paths (relative to source directory) of source files matching glob pattern from manifest, sorted. Absolute patterns and patterns with `..` components are rejected, so matched files stay within source directory.
+    pub fn glob_source_files(&self, name: &str, pattern: &str) -> AssetResult<Vec<PathBuf>, E> {
+        let pattern_path = Path::new(pattern);
+        if is_absolute_path(pattern_path)
+            || pattern_path
+                .components()
+                .any(|component| component == Component::ParentDir)
+        {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(
+                PathBuf::from(pattern),
+            )));
+        }
+        let source_root = self.source_root.to_str().ok_or_else(|| {
+            AssetError::new(AssetErrorType::AssetPathError(self.source_root.clone()))
+        })?;
+        let full_pattern = format!("{}/{}", glob::Pattern::escape(source_root), pattern);
+        let paths = glob::glob(&full_pattern).map_err(|err| {
+            AssetError::new(AssetErrorType::ManifestParseError {
+                asset: Some(name.to_string()),
+                detail: format!("invalid glob pattern {:?}: {}", pattern, err),
+            })
+        })?;
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let path = path.map_err(std::io::Error::from)?;
+            if !path.is_file() {
+                continue;
+            }
+            if let Ok(relative_path) = path.strip_prefix(&self.source_root) {
+                files.push(relative_path.to_path_buf());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Return `true` if path has root or prefix (like Windows drive).
+pub(crate) fn is_absolute_path(path: &Path) -> bool {
+    path.has_root() || matches!(path.components().next(), Some(Component::Prefix(_)))
+}
+
+/// Return source directory path and additional source directory paths from config, canonicalized relative to current working directory (if possible).
+pub(crate) fn canonical_source_roots(config: &AssetConfig) -> Vec<PathBuf> {
+    std::iter::once(&config.source_directory_path)
+        .chain(&config.source_directory_paths)
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect()
+}
+
+/// Resolve source file path from manifest against given source roots in order, first existing file is used. Absolute paths are rejected unless allowed by config.
+pub(crate) fn resolve_source_file_path<E>(
+    config: &AssetConfig,
+    source_roots: &[PathBuf],
+    path: &Path,
+) -> AssetResult<PathBuf, E>
+where
+    E: AssetFilterError,
+{
+    if is_absolute_path(path) {
+        if !config.allow_absolute_sources {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(
+                path.to_path_buf(),
+            )));
+        }
+        if path.exists() {
+            return Ok(path.to_path_buf());
+        }
+    } else {
+        for source_root in source_roots {
+            let full_path = source_root.join(path);
+            if full_path.exists() {
+                return Ok(full_path);
+            }
+        }
+    }
+    Err(AssetError::new(AssetErrorType::AssetSourceNotFound(
+        path.to_path_buf(),
+    )))
+}
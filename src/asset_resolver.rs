@@ -0,0 +1,56 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    asset_cache::AssetCacheManifestV1,
+    assets::{AssetFilterError, AssetResult},
+    load_cache_manifest,
+};
+
+/// Lookup of public asset file paths by asset name, for runtime (for example, to generate URLs in web server templates). Only public assets are exposed, intermediate assets can not be resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetResolver {
+    paths: HashMap<String, PathBuf>,
+}
+
+impl AssetResolver {
+    /// Create resolver from cache manifest.
+    pub fn new(cache_manifest: &AssetCacheManifestV1) -> Self {
+        AssetResolver {
+            paths: cache_manifest
+                .map
+                .values()
+                .filter(|cache_entry| cache_entry.is_public)
+                .map(|cache_entry| (cache_entry.name.clone(), cache_entry.path.clone()))
+                .collect(),
+        }
+    }
+
+    /// Create resolver from cache manifest file.
+    pub fn load<E>(cache_manifest_path: &Path) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        Ok(Self::new(&load_cache_manifest(cache_manifest_path)?))
+    }
+
+    /// Return path of public asset file relative to target directory.
+    pub fn resolve(&self, name: &str) -> Option<&Path> {
+        self.paths.get(name).map(PathBuf::as_path)
+    }
+
+    /// Return URL of public asset: base URL joined with asset file path by `/` (for example, `/static` and `css/app.css` give `/static/css/app.css`).
+    pub fn resolve_url(&self, name: &str, base_url: &str) -> Option<String> {
+        let path = self.resolve(name)?;
+        let mut url = base_url.trim_end_matches('/').to_string();
+        for component in path.components() {
+            if let Component::Normal(component) = component {
+                url.push('/');
+                url.push_str(&component.to_string_lossy());
+            }
+        }
+        Some(url)
+    }
+}
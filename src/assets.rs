@@ -1,9 +1,23 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+};
 
 use backtrace::Backtrace;
-use serde::{Deserialize, Serialize};
+use log::Level;
+use path_dedot::ParseDot;
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::asset_filter::AssetFilterOption;
+use crate::{
+    asset_cache::{content_hash, AssetHash},
+    asset_config::{AssetConfig, HashFormat, NamingStrategy},
+    asset_filter::{base64_bytes, AssetFilterOption, AssetFilterRegistry},
+    asset_pack::{canonical_source_roots, resolve_source_file_path},
+    dependency_graph::DependencyGraph,
+};
 
 pub trait AssetFilterError {}
 
@@ -39,6 +53,189 @@ where
     AssetFilterNotFoundError(String),
     AssetNotFoundInManifestError(String),
     AssetPathError(PathBuf),
+    /// Banner is set for asset with extension that does not support comments.
+    BannerNotSupportedError(String),
+    /// Filters used in manifest, but not registered in filter registry (sorted list of filter names).
+    MissingFiltersError(Vec<String>),
+    /// Asset is used as filter input, but its required features are not enabled.
+    AssetDisabledError(String),
+    /// Asset is generated by filter with empty list of inputs, but filter does not accept empty inputs.
+    NoFilterInputs(String),
+    /// Manifest has invalid structure. Asset name is set if error is in definition of specific asset.
+    ManifestParseError {
+        asset: Option<String>,
+        detail: String,
+    },
+    /// Filter input file has invalid content.
+    FilterInputError {
+        path: PathBuf,
+        detail: String,
+    },
+    /// Filter option has invalid type or value.
+    InvalidFilterOption {
+        option: String,
+        detail: String,
+    },
+    /// Output path of asset can not be predicted without building it.
+    OutputPathNotPredictable(String),
+    /// Asset is nested deeper than `max_dependency_depth` from config.
+    DependencyTooDeep {
+        asset: String,
+        depth: usize,
+    },
+    /// Assets use each other as inputs (asset names forming cycle, starting from smallest name).
+    DependencyCycle(Vec<String>),
+    /// Content-hashed file name of asset is already used by file with different content (hash is truncated too much).
+    OutputHashCollision {
+        asset: String,
+        path: PathBuf,
+    },
+    /// Required filter option is not set or has wrong type.
+    MissingFilterOption(String),
+    /// Internal directory is locked by another pack run (path of lock file).
+    LockError(PathBuf),
+    /// Source file (path from manifest) is not found in any source directory.
+    AssetSourceNotFound(PathBuf),
+    /// Asset is defined differently in merged manifests.
+    ConflictingAssetError(String),
+    /// Asset listed in public assets is not defined in manifest.
+    PublicAssetNotDefined(String),
+    /// Environment variable used in filter option is not set.
+    UndefinedEnvVar(String),
+    /// Asset listed in public assets has `Glob` source, such assets can not be public.
+    PublicGlobAsset(String),
+    /// Filtered asset is rebuilt by asynchronous pack run, but its filter output was not prepared (for example, internal file was removed after asset was checked).
+    FilterOutputNotPrepared(String),
+    /// Hash format of asset is invalid (see `HashFormat::is_valid`).
+    InvalidHashFormat(String),
+}
+
+/// Message names asset, filter or path involved. Wrapped I/O, JSON and filter errors are not included in message, they are returned by `source`.
+impl<E> fmt::Display for AssetErrorType<E>
+where
+    E: AssetFilterError,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetErrorType::IOError(_) => write!(f, "I/O error"),
+            AssetErrorType::JSONError(_) => write!(f, "JSON error"),
+            AssetErrorType::FilterError(_) => write!(f, "filter error"),
+            AssetErrorType::AssetFilterNotFoundError(filter_name) => {
+                write!(f, "filter {} is not found", filter_name)
+            }
+            AssetErrorType::AssetNotFoundInManifestError(name) => {
+                write!(f, "asset {} is not found in manifest", name)
+            }
+            AssetErrorType::AssetPathError(path) => {
+                write!(f, "invalid asset path {:?}", path)
+            }
+            AssetErrorType::BannerNotSupportedError(extension) => {
+                write!(f, "banner is not supported for extension {}", extension)
+            }
+            AssetErrorType::MissingFiltersError(filter_names) => {
+                write!(f, "filters {:?} are not registered", filter_names)
+            }
+            AssetErrorType::AssetDisabledError(name) => {
+                write!(f, "asset {} is disabled by features", name)
+            }
+            AssetErrorType::NoFilterInputs(name) => {
+                write!(f, "asset {} has no filter inputs", name)
+            }
+            AssetErrorType::ManifestParseError {
+                asset: Some(asset),
+                detail,
+            } => write!(
+                f,
+                "invalid manifest definition of asset {}: {}",
+                asset, detail
+            ),
+            AssetErrorType::ManifestParseError {
+                asset: None,
+                detail,
+            } => write!(f, "invalid manifest: {}", detail),
+            AssetErrorType::FilterInputError { path, detail } => {
+                write!(f, "invalid filter input {:?}: {}", path, detail)
+            }
+            AssetErrorType::InvalidFilterOption { option, detail } => {
+                write!(f, "invalid filter option {}: {}", option, detail)
+            }
+            AssetErrorType::OutputPathNotPredictable(name) => {
+                write!(f, "output path of asset {} can not be predicted", name)
+            }
+            AssetErrorType::DependencyTooDeep { asset, depth } => {
+                write!(f, "asset {} is nested too deep (depth {})", asset, depth)
+            }
+            AssetErrorType::DependencyCycle(cycle) => {
+                write!(f, "dependency cycle of assets {}", cycle.join(" -> "))
+            }
+            AssetErrorType::OutputHashCollision { asset, path } => {
+                write!(
+                    f,
+                    "hashed path {:?} of asset {} is already used",
+                    path, asset
+                )
+            }
+            AssetErrorType::MissingFilterOption(option) => {
+                write!(
+                    f,
+                    "required filter option {} is not set or has wrong type",
+                    option
+                )
+            }
+            AssetErrorType::LockError(path) => {
+                write!(f, "lock {:?} is held by another pack run", path)
+            }
+            AssetErrorType::AssetSourceNotFound(path) => {
+                write!(f, "source file {:?} is not found", path)
+            }
+            AssetErrorType::ConflictingAssetError(name) => {
+                write!(f, "asset {} has conflicting definitions", name)
+            }
+            AssetErrorType::PublicAssetNotDefined(name) => {
+                write!(f, "public asset {} is not defined in manifest", name)
+            }
+            AssetErrorType::UndefinedEnvVar(name) => {
+                write!(f, "environment variable {} is not set", name)
+            }
+            AssetErrorType::PublicGlobAsset(name) => {
+                write!(f, "glob asset {} can not be public", name)
+            }
+            AssetErrorType::FilterOutputNotPrepared(name) => write!(
+                f,
+                "output of asset {} was not prepared by asynchronous filter",
+                name
+            ),
+            AssetErrorType::InvalidHashFormat(name) => write!(
+                f,
+                "hash format of asset {} has zero length or length with UUID encoding",
+                name
+            ),
+        }
+    }
+}
+
+impl<E> fmt::Display for AssetError<E>
+where
+    E: AssetFilterError,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error_type.fmt(f)
+    }
+}
+
+/// Wrapped I/O, JSON and filter errors are returned as source. Asset error is converted to `anyhow::Error` (or `Box<dyn std::error::Error>`) by their blanket conversions if filter error type is also `Send + Sync`, separate conversion to `anyhow::Error` would conflict with them.
+impl<E> std::error::Error for AssetError<E>
+where
+    E: AssetFilterError + std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.error_type {
+            AssetErrorType::IOError(err) => Some(err),
+            AssetErrorType::JSONError(err) => Some(err),
+            AssetErrorType::FilterError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl<E> From<std::io::Error> for AssetError<E>
@@ -86,6 +283,12 @@ pub enum AssetSource {
     File(PathBuf),
     /// Result of processing other assets by filter.
     Filtered(AssetFiltered),
+    /// Content written in manifest (as base64 string), for small generated assets without source file.
+    Inline(#[serde(with = "base64_bytes")] Vec<u8>),
+    /// Directory from source directory with all its files (recursively). Files are copied to asset directory in internal storage and published as asset directory in target directory. When asset is used as filter input, filter receives all its files as separate inputs (sorted by path).
+    Directory(PathBuf),
+    /// All files from source directory matching glob pattern (sorted by path). Files are copied to asset directory in internal storage, asset can not be public. When asset is used as filter input, filter receives all its files as separate inputs.
+    Glob(String),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -95,9 +298,33 @@ pub struct AssetFiltered {
     /// Input asset names.
     pub input_names: Vec<String>,
     /// Other options passed to filter.
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub options: HashMap<String, AssetFilterOption>,
 }
 
+/// Strategy to detect if asset should be rebuilt.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChangeDetection {
+    /// Rebuild if source file hash or asset definition is changed, or if any input is rebuilt.
+    #[default]
+    Hash,
+    /// Same as `Hash`, but compare source file modification time instead of hash.
+    Mtime,
+    /// Rebuild on every pack run.
+    Always,
+    /// Build once and never rebuild unless internal file is missing.
+    Never,
+}
+
+/// Kind of non-hashed alias created for public asset alongside hashed file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StableAlias {
+    /// Symbolic link to hashed file (copy on platforms without symbolic links).
+    Symlink,
+    /// Copy of hashed file.
+    Copy,
+}
+
 /// Asset definition.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct AssetData {
@@ -107,10 +334,442 @@ pub struct AssetData {
     pub extension: String,
     /// Asset source definition.
     pub source: AssetSource,
+    /// Strategy to detect if asset should be rebuilt.
+    #[serde(default)]
+    pub change_detection: ChangeDetection,
+    /// Text prepended to public asset file as comment, for example build date and version. Comment syntax is inferred from extension.
+    #[serde(default)]
+    pub banner: Option<String>,
+    /// Create non-hashed alias (for example `style.css` for `style-UUID.css`) for public asset in target directory.
+    #[serde(default)]
+    pub stable_alias: Option<StableAlias>,
+    /// Build features that should be enabled to build this asset.
+    #[serde(default)]
+    pub required_features: Vec<String>,
+    /// Log details of processing of this asset (paths, hashes, rebuild reason, filter options) with `Info` level instead of `Debug`.
+    #[serde(default)]
+    pub debug: bool,
+    /// Format of content hash in file names, overrides `hash_format` from config.
+    #[serde(default)]
+    pub hash_format: Option<HashFormat>,
+    /// Permissions of public asset files in target directories (for example `0o755`), applied on Unix only.
+    #[serde(default)]
+    pub mode: Option<u32>,
+}
+
+impl AssetData {
+    /// Return log level for messages about processing of this asset.
+    pub fn log_level(&self) -> Level {
+        if self.debug {
+            Level::Info
+        } else {
+            Level::Debug
+        }
+    }
+
+    /// Return `true` if all required features of asset are enabled.
+    pub fn is_enabled(&self, enabled_features: &HashSet<String>) -> bool {
+        self.required_features
+            .iter()
+            .all(|feature| enabled_features.contains(feature))
+    }
+
+    /// Build asset file path (relative to internal or target directory) with `ContentHash` naming strategy for given content hash.
+    pub fn content_path(&self, name: &str, hash: &[u8], config: &AssetConfig) -> PathBuf {
+        let hash_format = self.hash_format.unwrap_or(config.hash_format);
+        self.output_path(name, &hash_format.encode(hash), config)
+    }
+
+    /// Build asset file path (relative to internal or target directory) for asset with given name and version suffix, using file name template from config. Resulting path is not checked.
+    pub fn output_path(&self, name: &str, suffix: &str, config: &AssetConfig) -> PathBuf {
+        let file_name =
+            render_filename_template(&config.filename_template, name, suffix, &self.extension);
+        match &self.output_base_path {
+            Some(base_path) => base_path.join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    }
+
+    /// Build file path (relative to internal or target directory) from output base path, given file stem and asset extension. Resulting path is not checked.
+    pub fn file_path(&self, file_stem: &str) -> PathBuf {
+        // Extension is appended as string, because `Path::with_extension` would replace part of file stem after dot.
+        let mut file_name = file_stem.to_string();
+        if !self.extension.is_empty() {
+            file_name.push('.');
+            file_name.push_str(&self.extension);
+        }
+        match &self.output_base_path {
+            Some(base_path) => base_path.join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    }
+}
+
+/// Render file name template (see `AssetConfig::filename_template`). Unknown placeholders are kept as is.
+fn render_filename_template(template: &str, name: &str, suffix: &str, extension: &str) -> String {
+    let mut result = String::with_capacity(template.len() + name.len() + suffix.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        match &rest[1..end] {
+            "name" => result.push_str(name),
+            "hash" | "uuid" => result.push_str(suffix),
+            "ext" if !extension.is_empty() => {
+                result.push('.');
+                result.push_str(extension);
+            }
+            "ext" => {}
+            _ => result.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Return `true` if path is relative and does not point outside of directory it is joined to, i.e. has no root, no prefix (like Windows drive or UNC prefix), and no `..` components leaving base directory.
+pub fn is_safe_relative_path(path: &Path) -> bool {
+    let mut depth: usize = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return false,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return false,
+            },
+            Component::Normal(_) => depth += 1,
+        }
+    }
+    true
+}
+
+/// Serialize map with keys in sorted order, so serialized output is deterministic.
+pub(crate) fn serialize_sorted_map<S, V>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    V: Serialize,
+{
+    map.iter()
+        .collect::<BTreeMap<&String, &V>>()
+        .serialize(serializer)
+}
+
+/// Format of asset manifest file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    /// TOML (requires `toml` feature). Filter options and sources are written as inline tables, for example `source = { File = "a.txt" }`.
+    #[cfg(feature = "toml")]
+    Toml,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ManifestFormat {
+    /// Detect format by file extension: `toml` is TOML (if `toml` feature is enabled), anything else is JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => ManifestFormat::Toml,
+            _ => ManifestFormat::Json,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct AssetManifest {
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub assets: HashMap<String, AssetData>,
     pub public_assets: Vec<String>,
 }
+
+impl AssetManifest {
+    /// Convert parsed JSON value to manifest. Asset definitions are checked one by one, so error names invalid asset if possible.
+    pub fn from_json_value<E>(value: serde_json::Value) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        if let Some(serde_json::Value::Object(assets)) = value.get("assets") {
+            for (name, data) in assets {
+                if let Err(err) = AssetData::deserialize(data) {
+                    return Err(AssetError::new(AssetErrorType::ManifestParseError {
+                        asset: Some(name.clone()),
+                        detail: err.to_string(),
+                    }));
+                }
+            }
+        }
+
+        serde_json::from_value(value).map_err(|err| {
+            AssetError::new(AssetErrorType::ManifestParseError {
+                asset: None,
+                detail: err.to_string(),
+            })
+        })
+    }
+
+    /// Read manifest from JSON reader. Invalid JSON syntax is reported as `JSONError`, invalid manifest structure as `ManifestParseError`.
+    pub fn from_reader<R, E>(reader: R) -> AssetResult<Self, E>
+    where
+        R: io::Read,
+        E: AssetFilterError,
+    {
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        Self::from_json_value(value)
+    }
+
+    /// Parse manifest from JSON string, see `from_reader`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<E>(manifest: &str) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        let value: serde_json::Value = serde_json::from_str(manifest)?;
+        Self::from_json_value(value)
+    }
+
+    /// Parse manifest from TOML string. Invalid TOML syntax and invalid manifest structure are reported as `ManifestParseError`.
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str<E>(manifest: &str) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        // TOML is converted to JSON value, so asset definitions are checked in the same way as in JSON manifests.
+        let value: serde_json::Value = toml::from_str(manifest).map_err(|err| {
+            AssetError::new(AssetErrorType::ManifestParseError {
+                asset: None,
+                detail: err.to_string(),
+            })
+        })?;
+        Self::from_json_value(value)
+    }
+
+    /// Read manifest in given format.
+    pub fn from_reader_with_format<R, E>(reader: R, format: ManifestFormat) -> AssetResult<Self, E>
+    where
+        R: io::Read,
+        E: AssetFilterError,
+    {
+        match format {
+            ManifestFormat::Json => Self::from_reader(reader),
+            #[cfg(feature = "toml")]
+            ManifestFormat::Toml => Self::from_toml_str(&io::read_to_string(reader)?),
+        }
+    }
+
+    /// Save manifest to JSON file. Assets and filter options are written in sorted order.
+    pub fn save(&self, manifest_path: &Path) -> io::Result<()> {
+        let manifest_file = File::create(manifest_path)?;
+        serde_json::to_writer_pretty(manifest_file, self)?;
+        Ok(())
+    }
+
+    /// Add assets and public assets of other manifest to this manifest. Public asset names already present are not repeated. Asset defined in both manifests must have identical definitions, otherwise `ConflictingAssetError` is returned and this manifest is not changed.
+    pub fn merge<E>(&mut self, other: AssetManifest) -> Result<(), AssetErrorType<E>>
+    where
+        E: AssetFilterError,
+    {
+        let mut conflicting_names: Vec<&String> = other
+            .assets
+            .iter()
+            .filter(|(name, data)| {
+                self.assets
+                    .get(*name)
+                    .is_some_and(|self_data| self_data != *data)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        conflicting_names.sort();
+        if let Some(name) = conflicting_names.first() {
+            return Err(AssetErrorType::ConflictingAssetError((*name).clone()));
+        }
+
+        self.assets.extend(other.assets);
+        for name in other.public_assets {
+            if !self.public_assets.contains(&name) {
+                self.public_assets.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that all filters used by filtered assets are registered in filter registry. Return error listing all missing filters.
+    pub fn check_filters<E>(&self, filter_registry: &AssetFilterRegistry<E>) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        self.check_filter_names(&|filter_name| filter_registry.has_filter(filter_name))
+    }
+
+    /// Check that predicate is `true` for names of all filters used in manifest.
+    pub(crate) fn check_filter_names<E>(
+        &self,
+        has_filter: &dyn Fn(&str) -> bool,
+    ) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let mut missing_filters: Vec<String> = self
+            .assets
+            .values()
+            .filter_map(|data| match &data.source {
+                AssetSource::Filtered(filtered) if !has_filter(&filtered.filter_name) => {
+                    Some(filtered.filter_name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        missing_filters.sort();
+        missing_filters.dedup();
+
+        if missing_filters.is_empty() {
+            Ok(())
+        } else {
+            Err(AssetError::new(AssetErrorType::MissingFiltersError(
+                missing_filters,
+            )))
+        }
+    }
+
+    /// Return names of assets loaded from source file with given path (relative to source directory), including directory assets containing it and glob assets matching it, sorted.
+    pub fn assets_with_source_file(&self, source_path: &Path) -> Vec<String> {
+        let source_path = source_path
+            .parse_dot()
+            .unwrap_or_else(|_| source_path.into());
+        let mut result: Vec<String> = self
+            .assets
+            .iter()
+            .filter(|(_, data)| match &data.source {
+                AssetSource::File(file_path) => {
+                    file_path.parse_dot().unwrap_or_else(|_| file_path.into()) == source_path
+                }
+                AssetSource::Directory(directory_path) => source_path.starts_with(
+                    directory_path
+                        .parse_dot()
+                        .unwrap_or_else(|_| directory_path.into()),
+                ),
+                AssetSource::Glob(pattern) => glob::Pattern::new(pattern).is_ok_and(|pattern| {
+                    pattern.matches_path_with(
+                        &source_path,
+                        glob::MatchOptions {
+                            require_literal_separator: true,
+                            ..glob::MatchOptions::new()
+                        },
+                    )
+                }),
+                AssetSource::Inline(_) | AssetSource::Filtered(_) => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        result.sort();
+        result
+    }
+
+    /// Predict path of asset file (relative to internal and target directories) before building it.
+    ///
+    /// Path can be predicted only with `ContentHash` naming strategy and only for assets loaded from source files (by hashing source file) and inline assets. Predicting path of filtered asset requires running filter, so `OutputPathNotPredictable` is returned for such assets (use path from cache manifest after pack run instead).
+    pub fn predict_output_path<E>(
+        &self,
+        name: &str,
+        config: &AssetConfig,
+    ) -> AssetResult<PathBuf, E>
+    where
+        E: AssetFilterError,
+    {
+        let data = self.assets.get(name).ok_or_else(|| {
+            AssetError::new(AssetErrorType::AssetNotFoundInManifestError(
+                name.to_string(),
+            ))
+        })?;
+
+        let output_hash = match (&data.source, config.naming) {
+            (AssetSource::File(file_path), NamingStrategy::ContentHash) => {
+                let source_full_path =
+                    resolve_source_file_path(config, &canonical_source_roots(config), file_path)?;
+                content_hash(config.hash_algorithm, &source_full_path)?
+            }
+            (AssetSource::Inline(bytes), NamingStrategy::ContentHash) => {
+                AssetHash::of_bytes(config.hash_algorithm, bytes)
+            }
+            _ => {
+                return Err(AssetError::new(AssetErrorType::OutputPathNotPredictable(
+                    name.to_string(),
+                )))
+            }
+        };
+
+        let output_path = data.content_path(name, &output_hash.hash, config);
+        if !is_safe_relative_path(&output_path) {
+            return Err(AssetError::new(AssetErrorType::AssetPathError(output_path)));
+        }
+        Ok(output_path)
+    }
+
+    /// Return names of assets that are not public and are not used by public assets directly or transitively, sorted.
+    pub fn unreachable_assets(&self) -> Vec<String> {
+        self.unreachable_assets_in_graph(&DependencyGraph::new(self))
+    }
+
+    /// Same as `unreachable_assets`, but use already built dependency graph.
+    pub fn unreachable_assets_in_graph(&self, graph: &DependencyGraph) -> Vec<String> {
+        let reachable = graph.with_inputs(&self.public_assets);
+        let mut result: Vec<String> = self
+            .assets
+            .keys()
+            .filter(|name| !reachable.contains(*name))
+            .cloned()
+            .collect();
+        result.sort();
+        result
+    }
+
+    /// Return names of given assets and all assets that depend on them directly or transitively.
+    pub fn with_dependents(&self, names: &[String]) -> HashSet<String> {
+        DependencyGraph::new(self).with_dependents(names)
+    }
+
+    /// Return names of public assets (in order of public asset list) that depend on source file with given path directly or transitively.
+    ///
+    /// Changed path may be either relative to source directory or prefixed with path of source directory or additional source directory (as given or canonicalized).
+    pub fn affected_public_assets(&self, changed: &Path, config: &AssetConfig) -> Vec<String> {
+        self.affected_public_assets_in_graph(&[changed], config, &DependencyGraph::new(self))
+    }
+
+    /// Same as `affected_public_assets`, but for multiple changed paths and with dependency graph built before.
+    pub fn affected_public_assets_in_graph(
+        &self,
+        changed_paths: &[&Path],
+        config: &AssetConfig,
+        graph: &DependencyGraph,
+    ) -> Vec<String> {
+        let source_directory_paths: Vec<&PathBuf> = std::iter::once(&config.source_directory_path)
+            .chain(&config.source_directory_paths)
+            .collect();
+        let source_roots = canonical_source_roots(config);
+
+        let mut changed_assets: Vec<String> = Vec::new();
+        for changed in changed_paths {
+            let source_path = source_directory_paths
+                .iter()
+                .copied()
+                .chain(&source_roots)
+                .find_map(|source_directory_path| changed.strip_prefix(source_directory_path).ok())
+                .unwrap_or(changed);
+            changed_assets.extend(self.assets_with_source_file(source_path));
+        }
+
+        let affected_assets = graph.with_dependents(&changed_assets);
+        self.public_assets
+            .iter()
+            .filter(|name| affected_assets.contains(*name))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, create_dir_all, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asset_cache::{
+        AssetCacheManifestV1, AssetCacheManifestVersioned, AssetCacheManifestVersionedRef,
+    },
+    asset_config::AssetConfig,
+    assets::{AssetError, AssetErrorType, AssetFilterError, AssetResult},
+};
+
+/// Name of bundle index file in archive.
+const INDEX_NAME: &str = "index.json";
+/// Name of cache manifest file in archive.
+const CACHE_MANIFEST_NAME: &str = "cache_manifest.json";
+/// Name of public manifest file (public asset names mapped to paths) in archive.
+const PUBLIC_MANIFEST_NAME: &str = "public_manifest.json";
+/// Directory with target files in archive.
+const TARGET_DIRECTORY_NAME: &str = "target";
+
+/// Top-level index of asset bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleIndex {
+    /// Path of cache manifest in archive.
+    pub cache_manifest: PathBuf,
+    /// Path of public manifest in archive.
+    pub public_manifest: PathBuf,
+    /// Directory with target files in archive.
+    pub target_directory: PathBuf,
+    /// Paths of target files relative to target directory, sorted.
+    pub files: Vec<PathBuf>,
+}
+
+impl AssetCacheManifestV1 {
+    /// Write tar archive with cache manifest, public manifest (public asset names mapped to file paths), all files from target directory and top-level `index.json` (see `BundleIndex`).
+    ///
+    /// Archive is reproducible: files are sorted by path, all entries have modification time `archive_timestamp` from config, mode `644` and no owner. Archive is compressed by gzip if output file name has extension `gz` or `tgz`.
+    pub fn export_bundle<E>(&self, config: &AssetConfig, out_tar: &Path) -> AssetResult<(), E>
+    where
+        E: AssetFilterError,
+    {
+        let out_file = File::create(out_tar)?;
+        let gzip = matches!(
+            out_tar.extension().and_then(|extension| extension.to_str()),
+            Some("gz") | Some("tgz")
+        );
+        if gzip {
+            let encoder = GzEncoder::new(out_file, Compression::default());
+            self.write_bundle(config, encoder)?.finish()?;
+        } else {
+            self.write_bundle(config, out_file)?;
+        }
+        Ok(())
+    }
+
+    fn write_bundle<W, E>(&self, config: &AssetConfig, writer: W) -> AssetResult<W, E>
+    where
+        W: Write,
+        E: AssetFilterError,
+    {
+        let mut files = Vec::new();
+        if config.target_directory_path.is_dir() {
+            list_files(&config.target_directory_path, Path::new(""), &mut files)?;
+        }
+        files.sort();
+
+        let public_manifest: BTreeMap<&String, &PathBuf> = self.public_index.iter().collect();
+        let index = BundleIndex {
+            cache_manifest: PathBuf::from(CACHE_MANIFEST_NAME),
+            public_manifest: PathBuf::from(PUBLIC_MANIFEST_NAME),
+            target_directory: PathBuf::from(TARGET_DIRECTORY_NAME),
+            files,
+        };
+
+        let mtime = config.archive_timestamp;
+        let mut builder = tar::Builder::new(writer);
+        append_data(
+            &mut builder,
+            Path::new(INDEX_NAME),
+            &serde_json::to_vec_pretty(&index)?,
+            mtime,
+        )?;
+        append_data(
+            &mut builder,
+            Path::new(CACHE_MANIFEST_NAME),
+            &serde_json::to_vec_pretty(&AssetCacheManifestVersionedRef::V1(self))?,
+            mtime,
+        )?;
+        append_data(
+            &mut builder,
+            Path::new(PUBLIC_MANIFEST_NAME),
+            &serde_json::to_vec_pretty(&public_manifest)?,
+            mtime,
+        )?;
+        for file in &index.files {
+            debug!("Adding {:?} to bundle", file);
+            append_data(
+                &mut builder,
+                &Path::new(TARGET_DIRECTORY_NAME).join(file),
+                &fs::read(config.target_directory_path.join(file))?,
+                mtime,
+            )?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    /// Unpack bundle written by `export_bundle` (compressed or not) into target directory. Return cache manifest from bundle.
+    ///
+    /// Bundle may contain only regular files, entries of other types (for example, symbolic links) cause `AssetPathError`.
+    pub fn import_bundle<E>(bundle: &Path, target_directory_path: &Path) -> AssetResult<Self, E>
+    where
+        E: AssetFilterError,
+    {
+        let mut bundle_file = BufReader::new(File::open(bundle)?);
+        // Gzip stream starts with magic bytes 1F 8B.
+        let gzip = bundle_file.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+        if gzip {
+            Self::read_bundle(GzDecoder::new(bundle_file), target_directory_path)
+        } else {
+            Self::read_bundle(bundle_file, target_directory_path)
+        }
+    }
+
+    fn read_bundle<R, E>(reader: R, target_directory_path: &Path) -> AssetResult<Self, E>
+    where
+        R: Read,
+        E: AssetFilterError,
+    {
+        let mut cache_manifest = None;
+
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.into_owned();
+            // Links and other special entries could make unpacked files escape target directory.
+            if !entry.header().entry_type().is_file() {
+                return Err(AssetError::new(AssetErrorType::AssetPathError(entry_path)));
+            }
+
+            if entry_path == Path::new(CACHE_MANIFEST_NAME) {
+                let cache_manifest_versioned: AssetCacheManifestVersioned =
+                    serde_json::from_reader(&mut entry)?;
+                cache_manifest = Some(match cache_manifest_versioned {
+                    AssetCacheManifestVersioned::V1(cache_manifest_v1) => cache_manifest_v1,
+                });
+            } else if let Ok(file_path) = entry_path.strip_prefix(TARGET_DIRECTORY_NAME) {
+                let is_normal = file_path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if !is_normal || file_path.components().next().is_none() {
+                    return Err(AssetError::new(AssetErrorType::AssetPathError(
+                        entry_path.clone(),
+                    )));
+                }
+                let full_path = target_directory_path.join(file_path);
+                debug!("Unpacking {:?} to {:?}", entry_path, full_path);
+                if let Some(full_path_parent) = full_path.parent() {
+                    create_dir_all(full_path_parent)?;
+                }
+                entry.unpack(&full_path)?;
+            }
+        }
+
+        cache_manifest.ok_or_else(|| {
+            AssetError::new(AssetErrorType::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bundle has no {}", CACHE_MANIFEST_NAME),
+            )))
+        })
+    }
+}
+
+/// Append paths of all files in directory (recursively) relative to given prefix.
+pub(crate) fn list_files(
+    directory_path: &Path,
+    prefix: &Path,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(directory_path)? {
+        let entry = entry?;
+        let relative_path = prefix.join(entry.file_name());
+        if entry.path().is_dir() {
+            list_files(&entry.path(), &relative_path, files)?;
+        } else {
+            files.push(relative_path);
+        }
+    }
+    Ok(())
+}
+
+/// Append file with given path and content to archive, with normalized metadata.
+fn append_data<W>(
+    builder: &mut tar::Builder<W>,
+    path: &Path,
+    bytes: &[u8],
+    mtime: u64,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder.append_data(&mut header, path, bytes)
+}
@@ -0,0 +1,51 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// Source of current time. Code paths that need current time take it from clock in pack context, so tests can control time.
+pub trait Clock {
+    /// Return current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock returning system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock returning manually set time, for tests.
+#[derive(Debug)]
+pub struct MockClock {
+    time: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    /// Create clock stopped at given time.
+    pub fn new(time: SystemTime) -> Self {
+        MockClock {
+            time: Mutex::new(time),
+        }
+    }
+
+    /// Set current time.
+    pub fn set(&self, time: SystemTime) {
+        *self.time.lock().unwrap() = time;
+    }
+
+    /// Move current time forward by given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.time.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.time.lock().unwrap()
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::assets::{AssetManifest, AssetSource};
+
+/// Dependency graph of manifest assets. Build it once and reuse for multiple queries.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Input names of each asset (in manifest order).
+    inputs: HashMap<String, Vec<String>>,
+    /// Names of assets that use each asset as input directly (sorted).
+    dependents: HashMap<String, Vec<String>>,
+    /// Source paths of assets loaded from source directory: file paths, directory paths and glob patterns.
+    sources: HashMap<String, PathBuf>,
+}
+
+impl DependencyGraph {
+    /// Build dependency graph of manifest assets.
+    pub fn new(manifest: &AssetManifest) -> Self {
+        let mut graph = DependencyGraph::default();
+
+        for (name, data) in &manifest.assets {
+            match &data.source {
+                AssetSource::File(file_path) => {
+                    graph.sources.insert(name.clone(), file_path.clone());
+                    graph.inputs.insert(name.clone(), Vec::new());
+                }
+                AssetSource::Directory(directory_path) => {
+                    graph.sources.insert(name.clone(), directory_path.clone());
+                    graph.inputs.insert(name.clone(), Vec::new());
+                }
+                AssetSource::Glob(pattern) => {
+                    graph.sources.insert(name.clone(), PathBuf::from(pattern));
+                    graph.inputs.insert(name.clone(), Vec::new());
+                }
+                AssetSource::Inline(_) => {
+                    graph.inputs.insert(name.clone(), Vec::new());
+                }
+                AssetSource::Filtered(filtered) => {
+                    for input_name in &filtered.input_names {
+                        graph
+                            .dependents
+                            .entry(input_name.clone())
+                            .or_default()
+                            .push(name.clone());
+                    }
+                    graph
+                        .inputs
+                        .insert(name.clone(), filtered.input_names.clone());
+                }
+            }
+        }
+        for dependents in graph.dependents.values_mut() {
+            dependents.sort();
+            dependents.dedup();
+        }
+
+        graph
+    }
+
+    /// Return names of direct inputs of asset.
+    pub fn inputs_of(&self, name: &str) -> &[String] {
+        self.inputs.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Return names of assets that use asset as input directly, sorted.
+    pub fn dependents_of(&self, name: &str) -> &[String] {
+        self.dependents
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return names of given assets and all assets that depend on them directly or transitively.
+    pub fn with_dependents(&self, names: &[String]) -> HashSet<String> {
+        let mut result: HashSet<String> = HashSet::new();
+        let mut stack: Vec<&str> = names.iter().map(String::as_str).collect();
+        while let Some(name) = stack.pop() {
+            if result.insert(name.to_string()) {
+                stack.extend(self.dependents_of(name).iter().map(String::as_str));
+            }
+        }
+        result
+    }
+
+    /// Return names of given assets and all assets they use as inputs directly or transitively.
+    pub fn with_inputs(&self, names: &[String]) -> HashSet<String> {
+        let mut result: HashSet<String> = HashSet::new();
+        let mut stack: Vec<&str> = names.iter().map(String::as_str).collect();
+        while let Some(name) = stack.pop() {
+            if result.insert(name.to_string()) {
+                stack.extend(self.inputs_of(name).iter().map(String::as_str));
+            }
+        }
+        result
+    }
+
+    /// Return source paths (as written in manifest: file paths, directory paths and glob patterns) that asset depends on directly or transitively, sorted.
+    pub fn transitive_sources(&self, name: &str) -> Vec<PathBuf> {
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut result: Vec<PathBuf> = Vec::new();
+        let mut stack: Vec<&str> = vec![name];
+        while let Some(name) = stack.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            if let Some(source_path) = self.sources.get(name) {
+                result.push(source_path.clone());
+            }
+            stack.extend(self.inputs_of(name).iter().map(String::as_str));
+        }
+        result.sort();
+        result.dedup();
+        result
+    }
+
+    /// Return asset names ordered so that every asset goes after its inputs. Assets without mutual dependencies are sorted by name. Return cycle (see `find_cycle`) if there is one.
+    pub fn topological_order(&self) -> Result<Vec<String>, Vec<String>> {
+        if let Some(cycle) = self.find_cycle() {
+            return Err(cycle);
+        }
+
+        let mut names: Vec<&str> = self.inputs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut result: Vec<String> = Vec::with_capacity(names.len());
+        for name in names {
+            self.visit_inputs_first(name, &mut visited, &mut result);
+        }
+        Ok(result)
+    }
+
+    /// Append asset and its unvisited inputs to result, inputs first. Explicit stack is used, so deep dependency chains do not overflow call stack.
+    fn visit_inputs_first<'a>(
+        &'a self,
+        name: &'a str,
+        visited: &mut HashSet<&'a str>,
+        result: &mut Vec<String>,
+    ) {
+        if !self.inputs.contains_key(name) || !visited.insert(name) {
+            return;
+        }
+        // Assets being visited with index of next input to visit.
+        let mut stack: Vec<(&str, usize)> = vec![(name, 0)];
+        while let Some((current, next_input)) = stack.last_mut() {
+            let current: &str = current;
+            match self.inputs_of(current).get(*next_input) {
+                Some(input_name) => {
+                    *next_input += 1;
+                    if self.inputs.contains_key(input_name) && visited.insert(input_name) {
+                        stack.push((input_name, 0));
+                    }
+                }
+                None => {
+                    result.push(current.to_string());
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Find dependency cycle. Return asset names forming cycle (each asset uses next one as input, last asset uses first one), starting from smallest name.
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut names: Vec<&str> = self.inputs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        let mut finished: HashSet<&str> = HashSet::new();
+        for name in names {
+            if let Some(cycle) = self.find_cycle_from(name, &mut finished) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    /// Search cycle by depth-first traversal from asset. Explicit stack is used, so deep dependency chains do not overflow call stack.
+    fn find_cycle_from<'a>(
+        &'a self,
+        name: &'a str,
+        finished: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if finished.contains(name) {
+            return None;
+        }
+
+        // Path of assets being visited, each asset uses next one as input, and index of next input to visit of each of them.
+        let mut path: Vec<&str> = vec![name];
+        let mut next_inputs: Vec<usize> = vec![0];
+        let mut on_path: HashSet<&str> = HashSet::from([name]);
+        while let (Some(&current), Some(next_input)) = (path.last(), next_inputs.last_mut()) {
+            match self.inputs_of(current).get(*next_input) {
+                Some(input_name) => {
+                    *next_input += 1;
+                    if finished.contains(input_name.as_str()) {
+                        continue;
+                    }
+                    if on_path.contains(input_name.as_str()) {
+                        return cycle_in_path(&path, input_name);
+                    }
+                    path.push(input_name);
+                    next_inputs.push(0);
+                    on_path.insert(input_name);
+                }
+                None => {
+                    path.pop();
+                    next_inputs.pop();
+                    on_path.remove(current);
+                    finished.insert(current);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// If asset is already in path of assets being visited (each asset uses next one as input), return cycle formed by re-entering it, starting from smallest name.
+pub(crate) fn cycle_in_path<S>(path: &[S], name: &str) -> Option<Vec<String>>
+where
+    S: AsRef<str>,
+{
+    let position = path.iter().position(|item| item.as_ref() == name)?;
+    let mut cycle: Vec<String> = path[position..]
+        .iter()
+        .map(|item| item.as_ref().to_string())
+        .collect();
+    let smallest = (0..cycle.len()).min_by_key(|index| &cycle[*index])?;
+    cycle.rotate_left(smallest);
+    Some(cycle)
+}
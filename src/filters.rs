@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    asset_filter::{get_string, AssetFilter, AssetFilterOption},
+    assets::{AssetError, AssetErrorType, AssetFilterError},
+};
+
+/// Filter that concatenates inputs in order (for example, to bundle scripts or styles). It has no options.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyFilter {}
+
+impl CopyFilter {
+    /// Conventional name of filter in filter registry.
+    pub const NAME: &'static str = "Copy";
+}
+
+impl<E> AssetFilter<E> for CopyFilter
+where
+    E: AssetFilterError,
+{
+    fn process_asset_file(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        _options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        if let Some(output_file_path_parent) = output_file_path.parent() {
+            create_dir_all(output_file_path_parent)?;
+        }
+        let mut output_file = File::create(output_file_path)?;
+        for input_file_path in input_file_paths {
+            io::copy(&mut File::open(input_file_path)?, &mut output_file)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Filter that concatenates inputs in order, inserting separator between them. Inputs are streamed to output, so they are not loaded into memory. Empty list of inputs produces empty file.
+///
+/// Options:
+///
+/// * `separator` (string, optional) is written between inputs, nothing is written by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConcatFilter {}
+
+impl ConcatFilter {
+    /// Conventional name of filter in filter registry.
+    pub const NAME: &'static str = "Concat";
+}
+
+impl<E> AssetFilter<E> for ConcatFilter
+where
+    E: AssetFilterError,
+{
+    fn process_asset_file(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        let separator = match options.get("separator").cloned().map(get_string) {
+            None => String::new(),
+            Some(Some(separator)) => separator,
+            Some(None) => {
+                return Err(AssetError::new(AssetErrorType::InvalidFilterOption {
+                    option: "separator".to_string(),
+                    detail: "expected string".to_string(),
+                }))
+            }
+        };
+
+        if let Some(output_file_path_parent) = output_file_path.parent() {
+            create_dir_all(output_file_path_parent)?;
+        }
+        let mut output_file = BufWriter::new(File::create(output_file_path)?);
+        for (index, input_file_path) in input_file_paths.iter().enumerate() {
+            if index > 0 {
+                output_file.write_all(separator.as_bytes())?;
+            }
+            io::copy(&mut File::open(input_file_path)?, &mut output_file)?;
+        }
+        output_file.flush()?;
+
+        Ok(())
+    }
+
+    fn accepts_empty_inputs(&self) -> bool {
+        true
+    }
+}
+
+/// How `JsonMergeFilter` merges arrays found under same key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonArrayMerge {
+    /// Later array replaces earlier one.
+    #[default]
+    Replace,
+    /// Later array is appended to earlier one.
+    Concat,
+}
+
+/// Filter that deep-merges JSON inputs in order: objects are merged key by key, other values from later inputs replace earlier ones.
+///
+/// Options:
+///
+/// * `arrays` (string, optional) is `"Replace"` (default) or `"Concat"`, see `JsonArrayMerge`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonMergeFilter {}
+
+impl JsonMergeFilter {
+    /// Conventional name of filter in filter registry.
+    pub const NAME: &'static str = "JsonMerge";
+
+    /// Merge `value` into `target`.
+    pub fn merge(target: &mut Value, value: Value, arrays: JsonArrayMerge) {
+        match (target, value) {
+            (Value::Object(target_map), Value::Object(map)) => {
+                for (key, value) in map {
+                    match target_map.get_mut(&key) {
+                        Some(target_value) => Self::merge(target_value, value, arrays),
+                        None => {
+                            target_map.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (Value::Array(target_array), Value::Array(array))
+                if arrays == JsonArrayMerge::Concat =>
+            {
+                target_array.extend(array);
+            }
+            (target, value) => *target = value,
+        }
+    }
+}
+
+impl<E> AssetFilter<E> for JsonMergeFilter
+where
+    E: AssetFilterError,
+{
+    fn process_asset_file(
+        &self,
+        input_file_paths: &[PathBuf],
+        output_file_path: &Path,
+        options: &HashMap<String, AssetFilterOption>,
+    ) -> Result<(), AssetError<E>> {
+        let arrays = match options.get("arrays").cloned().map(get_string) {
+            None => JsonArrayMerge::default(),
+            Some(Some(value)) if value == "Replace" => JsonArrayMerge::Replace,
+            Some(Some(value)) if value == "Concat" => JsonArrayMerge::Concat,
+            Some(_) => {
+                return Err(AssetError::new(AssetErrorType::InvalidFilterOption {
+                    option: "arrays".to_string(),
+                    detail: "expected string \"Replace\" or \"Concat\"".to_string(),
+                }))
+            }
+        };
+
+        let mut result: Option<Value> = None;
+        for input_file_path in input_file_paths {
+            let input_file = BufReader::new(File::open(input_file_path)?);
+            let value: Value = serde_json::from_reader(input_file).map_err(|err| {
+                AssetError::new(AssetErrorType::FilterInputError {
+                    path: input_file_path.clone(),
+                    detail: err.to_string(),
+                })
+            })?;
+            match &mut result {
+                Some(result) => Self::merge(result, value, arrays),
+                None => result = Some(value),
+            }
+        }
+
+        if let Some(output_file_path_parent) = output_file_path.parent() {
+            create_dir_all(output_file_path_parent)?;
+        }
+        let output_file = File::create(output_file_path)?;
+        serde_json::to_writer(output_file, &result.unwrap_or(Value::Null))?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,30 @@
 pub mod asset_cache;
 pub mod asset_config;
 pub mod asset_filter;
+#[cfg(feature = "async")]
+pub mod asset_filter_async;
+pub mod asset_pack;
+pub mod asset_resolver;
 pub mod assets;
+pub mod bundle;
+pub mod clock;
+pub mod dependency_graph;
+pub mod filters;
+pub mod lint;
+pub mod observer;
+pub mod runtime_manifest;
 mod test;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "notify")]
+pub mod watch;
 
-use std::{fs::File, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
 
 use log::debug;
 
@@ -12,7 +32,14 @@ use crate::{
     asset_cache::{AssetCacheManifest, AssetCacheManifestVersioned},
     asset_config::AssetConfig,
     asset_filter::AssetFilterRegistry,
-    assets::{AssetFilterError, AssetManifest, AssetResult},
+    asset_pack::{BuildPlan, PackContext, PackLock, PackReport, ShouldRebuild},
+    assets::{
+        AssetError, AssetErrorType, AssetFilterError, AssetManifest, AssetResult, AssetSource,
+        ManifestFormat,
+    },
+    dependency_graph::DependencyGraph,
+    observer::PackObserver,
+    runtime_manifest::WebManifestEntry,
 };
 
 /// Load cache manifest from file.
@@ -32,49 +59,522 @@ where
     }
 }
 
+/// Load asset manifest from file, format is detected by file extension (see `ManifestFormat::from_path`). Invalid JSON syntax is reported as `JSONError`, invalid manifest structure as `ManifestParseError`.
+pub fn load_manifest<E>(manifest_path: &Path) -> AssetResult<AssetManifest, E>
+where
+    E: AssetFilterError,
+{
+    load_manifest_with_format(manifest_path, ManifestFormat::from_path(manifest_path))
+}
+
+/// Load asset manifest from file in given format.
+pub fn load_manifest_with_format<E>(
+    manifest_path: &Path,
+    format: ManifestFormat,
+) -> AssetResult<AssetManifest, E>
+where
+    E: AssetFilterError,
+{
+    let manifest_file = BufReader::new(File::open(manifest_path)?);
+    AssetManifest::from_reader_with_format(manifest_file, format)
+}
+
+/// Load asset manifests from files and merge them in given order (see `AssetManifest::merge`).
+pub fn load_manifests<E>(manifest_paths: &[PathBuf]) -> AssetResult<AssetManifest, E>
+where
+    E: AssetFilterError,
+{
+    let mut manifest = AssetManifest::default();
+    for manifest_path in manifest_paths {
+        manifest
+            .merge(load_manifest(manifest_path)?)
+            .map_err(AssetError::new)?;
+    }
+    Ok(manifest)
+}
+
+/// Check manifest before processing: all public assets and filter inputs are defined, all filters are registered, hash formats of assets are valid and there are no dependency cycles. Return all problems found: undefined public assets (`PublicAssetNotDefined`) in order of public assets, invalid hash formats of assets (`InvalidHashFormat`) and undefined filter inputs (`AssetNotFoundInManifestError`) by name of asset, missing filters (`AssetFilterNotFoundError`) sorted by filter name, and `DependencyCycle`.
+pub fn validate_manifest<E>(
+    manifest: &AssetManifest,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> Result<(), Vec<AssetErrorType<E>>>
+where
+    E: AssetFilterError,
+{
+    let mut errors: Vec<AssetErrorType<E>> = Vec::new();
+
+    for name in &manifest.public_assets {
+        match manifest.assets.get(name).map(|data| &data.source) {
+            None => errors.push(AssetErrorType::PublicAssetNotDefined(name.clone())),
+            Some(AssetSource::Glob(_)) => {
+                errors.push(AssetErrorType::PublicGlobAsset(name.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut names: Vec<&String> = manifest.assets.keys().collect();
+    names.sort();
+    let mut missing_filters: Vec<String> = Vec::new();
+    for name in names {
+        let data = &manifest.assets[name];
+        if data
+            .hash_format
+            .is_some_and(|hash_format| !hash_format.is_valid())
+        {
+            errors.push(AssetErrorType::InvalidHashFormat(name.clone()));
+        }
+        if let AssetSource::Filtered(filtered) = &data.source {
+            for input_name in &filtered.input_names {
+                if !manifest.assets.contains_key(input_name) {
+                    errors.push(AssetErrorType::AssetNotFoundInManifestError(
+                        input_name.clone(),
+                    ));
+                }
+            }
+            if !filter_registry.has_filter(&filtered.filter_name) {
+                missing_filters.push(filtered.filter_name.clone());
+            }
+        }
+    }
+    missing_filters.sort();
+    missing_filters.dedup();
+    errors.extend(
+        missing_filters
+            .into_iter()
+            .map(AssetErrorType::AssetFilterNotFoundError),
+    );
+
+    if let Some(cycle) = DependencyGraph::new(manifest).find_cycle() {
+        errors.push(AssetErrorType::DependencyCycle(cycle));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Save cache manifest to file.
+pub fn save_cache_manifest<E>(
+    cache_manifest_path: &Path,
+    cache_manifest: &AssetCacheManifest,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    cache_manifest.save(cache_manifest_path)
+}
+
+/// Restore missing internal asset files from target directory using cache manifest. Return sorted names of assets that could not be restored and are rebuilt on next pack run.
+pub fn rehydrate_internal<E>(
+    config: &AssetConfig,
+    cache_manifest: &AssetCacheManifest,
+) -> AssetResult<Vec<String>, E>
+where
+    E: AssetFilterError,
+{
+    cache_manifest.rehydrate_internal(config)
+}
+
 /// Process asset manifest and asset cache manifest stored in files. Generate new asset versions if needed.
+///
+/// Cache manifest is saved after every rebuilt public asset and on failure, so assets built before an error are not rebuilt on the next run. Internal directory is locked during pack run (see `PackLock`), so simultaneous pack run fails with `LockError`.
 pub fn pack<E>(
     manifest_path: &Path,
     cache_manifest_path: &Path,
     config: &AssetConfig,
     filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    pack_manifest(&manifest, cache_manifest_path, config, filter_registry)
+}
+
+/// Same as `pack`, but use already loaded asset manifest (for example, parsed by `AssetManifest::from_str` from string embedded into binary).
+pub fn pack_manifest<E>(
+    manifest: &AssetManifest,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    pack_public_assets(
+        manifest,
+        &manifest.public_assets,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        PackRunOptions::default(),
+    )
+}
+
+/// Write web manifest: JSON dictionary mapping public asset names to file paths (relative to target directory) and Subresource Integrity strings, see `WebManifestEntry`.
+pub fn write_web_manifest<E>(
+    cache_manifest: &AssetCacheManifest,
+    web_manifest_path: &Path,
 ) -> AssetResult<(), E>
 where
     E: AssetFilterError,
 {
-    let manifest: AssetManifest;
+    let web_manifest: BTreeMap<&String, WebManifestEntry> = cache_manifest
+        .map
+        .values()
+        .filter(|cache_entry| cache_entry.is_public)
+        .map(|cache_entry| {
+            (
+                &cache_entry.name,
+                WebManifestEntry {
+                    path: cache_entry.path.clone(),
+                    integrity: cache_entry.integrity.clone(),
+                },
+            )
+        })
+        .collect();
+
+    let web_manifest_file = File::create(web_manifest_path)?;
+    serde_json::to_writer_pretty(web_manifest_file, &web_manifest)?;
+    Ok(())
+}
+
+/// Check public assets like `pack` does (source files are read and hashed), but do not write or remove any files and do not run filters. Return plan with assets that would be rebuilt.
+///
+/// As filters are not run, filtered assets with rebuilt inputs are always reported (as `tentative`, see `PlannedRebuild`), though `pack` skips them if rebuilt inputs have same content as before.
+pub fn pack_dry_run<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<BuildPlan, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+    manifest.check_filters(filter_registry)?;
+
+    let cache_manifest = load_cache_manifest(cache_manifest_path)?;
+    cache_manifest.plan(config, &manifest, filter_registry)
+}
+
+/// Same as `pack`, but copy to target directory only public assets with names matching predicate.
+///
+/// Non-matching public assets are still built into internal directory.
+pub fn pack_matching<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    predicate: &dyn Fn(&str) -> bool,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    pack_public_assets(
+        &manifest,
+        &manifest.public_assets,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        PackRunOptions {
+            predicate,
+            ..PackRunOptions::default()
+        },
+    )
+}
+
+/// Same as `pack`, but notify observer when assets are processed (for example, to show progress).
+pub fn pack_with_observer<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    observer: &dyn PackObserver,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    pack_public_assets(
+        &manifest,
+        &manifest.public_assets,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        PackRunOptions {
+            observer: Some(observer),
+            ..PackRunOptions::default()
+        },
+    )
+}
+
+/// Same as `pack`, but consult callback before rebuilding cached assets: `Some(true)` forces rebuild, `Some(false)` skips it (unless internal file is missing), `None` leaves decision to change detection.
+pub fn pack_with_should_rebuild<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    should_rebuild: &ShouldRebuild,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    pack_public_assets(
+        &manifest,
+        &manifest.public_assets,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        PackRunOptions {
+            should_rebuild: Some(should_rebuild),
+            ..PackRunOptions::default()
+        },
+    )
+}
+
+/// Same as `pack`, but process only public assets affected by changes of given source files.
+///
+/// Changed paths may be either relative to source directory or prefixed with source directory path.
+pub fn pack_changed<E>(
+    changed_paths: &[PathBuf],
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    pack_changed_in_graph(
+        changed_paths,
+        &manifest,
+        &DependencyGraph::new(&manifest),
+        cache_manifest_path,
+        config,
+        filter_registry,
+    )
+}
+
+/// Same as `pack_changed`, but use already loaded asset manifest and its dependency graph, so they are not rebuilt for every run (for example, when watching source directories).
+pub fn pack_changed_in_graph<E>(
+    changed_paths: &[PathBuf],
+    manifest: &AssetManifest,
+    graph: &DependencyGraph,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let changed_paths: Vec<&Path> = changed_paths.iter().map(PathBuf::as_path).collect();
+    let public_assets = manifest.affected_public_assets_in_graph(&changed_paths, config, graph);
+    debug!(
+        "Public assets affected by changed files: {:?}",
+        public_assets
+    );
+
+    pack_public_assets(
+        manifest,
+        &public_assets,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        PackRunOptions::default(),
+    )
+}
+
+/// Same as `pack`, but process only asset with given name (and its inputs). Public asset is copied to target directory, other asset is only built into internal directory.
+pub fn pack_single<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    name: &str,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+    if !manifest.assets.contains_key(name) {
+        return Err(AssetError::new(
+            AssetErrorType::AssetNotFoundInManifestError(name.to_string()),
+        ));
+    }
+
+    if manifest
+        .public_assets
+        .iter()
+        .any(|public_name| public_name == name)
     {
-        let manifest_file = File::open(manifest_path)?;
-        manifest = serde_json::from_reader(manifest_file)?;
+        return pack_public_assets(
+            &manifest,
+            &[name.to_string()],
+            cache_manifest_path,
+            config,
+            filter_registry,
+            PackRunOptions::default(),
+        );
     }
 
-    let cache_manifest: AssetCacheManifestVersioned = if cache_manifest_path.exists() {
-        let cache_manifest_file = std::fs::File::open(cache_manifest_path)?;
-        serde_json::from_reader(cache_manifest_file)?
-    } else {
-        AssetCacheManifestVersioned::default()
-    };
+    manifest.check_filters(filter_registry)?;
 
-    match cache_manifest {
-        AssetCacheManifestVersioned::V1(mut cache_manifest_v1) => {
-            debug!("Processing assets...");
+    let _lock = PackLock::acquire(config)?;
+    let mut cache_manifest = load_cache_manifest(cache_manifest_path)?;
 
-            let result =
-                cache_manifest_v1.process_public_assets(config, &manifest, filter_registry);
+    debug!("Processing asset {}...", name);
 
-            if result.is_ok() {
-                debug!("Assets were processed");
-            }
+    let mut context = PackContext::new(config, &manifest, filter_registry);
+    // Assets built before error are saved too.
+    let result = cache_manifest.process(name.to_string(), &mut context);
+    save_cache_manifest(cache_manifest_path, &cache_manifest)?;
+    result?;
 
-            {
-                let cache_manifest_file = std::fs::File::create(cache_manifest_path)?;
-                serde_json::to_writer(
-                    cache_manifest_file,
-                    &AssetCacheManifestVersioned::V1(cache_manifest_v1),
-                )?;
-            }
+    Ok(context.report)
+}
+
+/// Same as `pack`, but filtered assets are built by asynchronous filters, which are awaited instead of blocking. Assets are processed one by one.
+#[cfg(feature = "async")]
+pub async fn pack_async<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &asset_filter_async::AssetFilterRegistryAsync<E>,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+    manifest.check_filters_async(filter_registry)?;
+
+    let _lock = PackLock::acquire(config)?;
+    let mut cache_manifest = load_cache_manifest(cache_manifest_path)?;
 
-            result
+    debug!("Processing assets...");
+
+    // Filter outputs are prepared by asynchronous filters, so synchronous registry is empty.
+    let empty_registry = AssetFilterRegistry::new(std::collections::HashMap::new());
+    let mut context = PackContext::new(config, &manifest, &empty_registry);
+    context.checkpoint_path = Some(cache_manifest_path);
+
+    cache_manifest
+        .process_public_asset_names_async(&manifest.public_assets, filter_registry, &mut context)
+        .await?;
+
+    debug!("Assets were processed");
+
+    cache_manifest.bump_generation();
+    save_cache_manifest(cache_manifest_path, &cache_manifest)?;
+
+    Ok(context.report)
+}
+
+/// Remove cache entries of assets not used by public assets of manifest and files in internal and target directories not referenced by cache manifest (see `AssetCacheManifestV1::prune`), save cache manifest. Return sorted full paths of removed files.
+pub fn clean<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+) -> AssetResult<Vec<PathBuf>, E>
+where
+    E: AssetFilterError,
+{
+    let manifest = load_manifest(manifest_path)?;
+
+    let _lock = PackLock::acquire(config)?;
+    let mut cache_manifest = load_cache_manifest(cache_manifest_path)?;
+    let pruned = cache_manifest.prune(config, &manifest)?;
+    save_cache_manifest(cache_manifest_path, &cache_manifest)?;
+
+    Ok(pruned)
+}
+
+/// Read list of changed file paths, one path per line (for example, from standard input of external file watcher).
+pub fn read_changed_paths<R>(reader: R) -> std::io::Result<Vec<PathBuf>>
+where
+    R: BufRead,
+{
+    let mut result = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            result.push(PathBuf::from(line));
+        }
+    }
+    Ok(result)
+}
+
+/// Options of pack run that differ between `pack` variants.
+struct PackRunOptions<'a> {
+    /// Only public assets with names matching predicate are copied to target directory.
+    predicate: &'a dyn Fn(&str) -> bool,
+    should_rebuild: Option<&'a ShouldRebuild>,
+    observer: Option<&'a dyn PackObserver>,
+}
+
+impl Default for PackRunOptions<'_> {
+    fn default() -> Self {
+        PackRunOptions {
+            predicate: &|_| true,
+            should_rebuild: None,
+            observer: None,
         }
     }
 }
+
+fn pack_public_assets<E>(
+    manifest: &AssetManifest,
+    public_assets: &[String],
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    options: PackRunOptions,
+) -> AssetResult<PackReport, E>
+where
+    E: AssetFilterError,
+{
+    manifest.check_filters(filter_registry)?;
+
+    let _lock = PackLock::acquire(config)?;
+    let mut cache_manifest = load_cache_manifest(cache_manifest_path)?;
+
+    debug!("Processing assets...");
+
+    let mut planning_context = PackContext::new(config, manifest, filter_registry);
+    planning_context.should_rebuild = options.should_rebuild;
+    // If plan can not be computed, assets are checked while processing, so assets before failing one are still built and saved.
+    let plan = cache_manifest
+        .plan_public_asset_names(public_assets, &planning_context)
+        .ok();
+    if let Some(plan) = &plan {
+        debug!("Assets to rebuild: {:?}", plan.names());
+    }
+
+    let mut context = PackContext::new(config, manifest, filter_registry);
+    context.checkpoint_path = Some(cache_manifest_path);
+    context.should_rebuild = options.should_rebuild;
+    context.observer = options.observer;
+    context.plan = plan.as_ref();
+
+    cache_manifest.process_public_asset_names(public_assets, options.predicate, &mut context)?;
+
+    debug!("Assets were processed");
+
+    // Checkpoints keep previous generation, so readers see new generation only for complete build.
+    cache_manifest.bump_generation();
+    save_cache_manifest(cache_manifest_path, &cache_manifest)?;
+
+    Ok(context.report)
+}
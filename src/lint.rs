@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+
+use crate::{
+    asset_cache::format_banner,
+    asset_config::{AssetConfig, HashFormat},
+    asset_filter::AssetFilterRegistry,
+    asset_pack::is_absolute_path,
+    assets::{is_safe_relative_path, AssetManifest, AssetSource},
+    dependency_graph::DependencyGraph,
+};
+
+/// Severity of lint finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    /// Manifest can not be packed.
+    Error,
+    /// Manifest can be packed, but probably has mistake.
+    Warning,
+}
+
+/// Kind of problem found by manifest linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// Filter input asset is not defined.
+    MissingInput(String),
+    /// Public asset is not defined.
+    MissingPublicAsset,
+    /// Assets use each other as inputs (asset names forming cycle, see `DependencyGraph::find_cycle`).
+    DependencyCycle(Vec<String>),
+    /// Asset file path points outside of internal and target directories.
+    UnsafeOutputPath(PathBuf),
+    /// Source file path is absolute, but absolute sources are not allowed by config.
+    AbsoluteSource(PathBuf),
+    /// Filter is not registered in filter registry.
+    MissingFilter(String),
+    /// Banner is set for extension that does not support comments.
+    BannerNotSupported(String),
+    /// Glob asset is public.
+    PublicGlob,
+    /// Asset is not public and is not used by public assets directly or transitively.
+    UnreachableAsset,
+    /// Hash format of asset (set by asset or config) has zero length or length with `Uuid` encoding.
+    InvalidHashFormat(HashFormat),
+}
+
+impl LintKind {
+    /// Return severity of finding of this kind.
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintKind::UnreachableAsset => LintSeverity::Warning,
+            _ => LintSeverity::Error,
+        }
+    }
+}
+
+/// Problem found by manifest linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    /// Name of asset with problem.
+    pub asset: String,
+    pub kind: LintKind,
+}
+
+impl LintFinding {
+    /// Return severity of finding.
+    pub fn severity(&self) -> LintSeverity {
+        self.kind.severity()
+    }
+}
+
+impl AssetManifest {
+    /// Check manifest without building it or reading source files. Return findings sorted by asset name.
+    pub fn lint<E>(
+        &self,
+        config: &AssetConfig,
+        filter_registry: &AssetFilterRegistry<E>,
+    ) -> Vec<LintFinding> {
+        let mut findings: Vec<LintFinding> = Vec::new();
+        let mut add_finding = |asset: &str, kind: LintKind| {
+            findings.push(LintFinding {
+                asset: asset.to_string(),
+                kind,
+            })
+        };
+
+        for name in &self.public_assets {
+            match self.assets.get(name).map(|data| &data.source) {
+                None => add_finding(name, LintKind::MissingPublicAsset),
+                Some(AssetSource::Glob(_)) => add_finding(name, LintKind::PublicGlob),
+                Some(_) => {}
+            }
+        }
+
+        for (name, data) in &self.assets {
+            let output_path = data.output_path(name, "0", config);
+            if !is_safe_relative_path(&output_path) {
+                add_finding(name, LintKind::UnsafeOutputPath(output_path));
+            }
+            let hash_format = data.hash_format.unwrap_or(config.hash_format);
+            if !hash_format.is_valid() {
+                add_finding(name, LintKind::InvalidHashFormat(hash_format));
+            }
+            if let Some(banner) = &data.banner {
+                if format_banner(banner, &data.extension).is_none() {
+                    add_finding(name, LintKind::BannerNotSupported(data.extension.clone()));
+                }
+            }
+
+            match &data.source {
+                AssetSource::File(file_path) => {
+                    if is_absolute_path(file_path) && !config.allow_absolute_sources {
+                        add_finding(name, LintKind::AbsoluteSource(file_path.clone()));
+                    }
+                }
+                AssetSource::Inline(_) | AssetSource::Directory(_) | AssetSource::Glob(_) => {}
+                AssetSource::Filtered(filtered) => {
+                    if !filter_registry.has_filter(&filtered.filter_name) {
+                        add_finding(name, LintKind::MissingFilter(filtered.filter_name.clone()));
+                    }
+                    for input_name in &filtered.input_names {
+                        if !self.assets.contains_key(input_name) {
+                            add_finding(name, LintKind::MissingInput(input_name.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let graph = DependencyGraph::new(self);
+        if let Some(cycle) = graph.find_cycle() {
+            add_finding(&cycle[0].clone(), LintKind::DependencyCycle(cycle));
+        }
+        for name in self.unreachable_assets_in_graph(&graph) {
+            add_finding(&name, LintKind::UnreachableAsset);
+        }
+
+        findings.sort_by(|finding1, finding2| finding1.asset.cmp(&finding2.asset));
+        findings
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::Mutex,
+    time::Instant,
+};
+
+use serde::Serialize;
+
+/// Observer of pack run events. All methods do nothing by default.
+pub trait PackObserver {
+    /// Asset processing is started (called once per asset per pack run, before its inputs are processed).
+    fn on_asset_start(&self, _name: &str) {}
+
+    /// Asset was rebuilt, internal file has given size in bytes.
+    fn on_asset_built(&self, _name: &str, _bytes: u64) {}
+
+    /// Asset is up to date and was not rebuilt.
+    fn on_asset_skipped(&self, _name: &str) {}
+}
+
+/// Observer ignoring all events.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl PackObserver for NoopObserver {}
+
+/// Build event written by `JsonEventObserver`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct JsonEvent<'a> {
+    event: &'a str,
+    asset: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+}
+
+/// Observer writing every event as JSON object on separate line (for example, `{"event":"rebuilt","asset":"main_css","ms":42,"bytes":1024}`). Events are `started`, `rebuilt` (with processing time in milliseconds and size in bytes) and `skipped`.
+///
+/// Write errors do not stop pack run, first error is returned by `finish`.
+pub struct JsonEventObserver<W>
+where
+    W: Write,
+{
+    writer: Mutex<W>,
+    started: Mutex<HashMap<String, Instant>>,
+    error: Mutex<Option<io::Error>>,
+}
+
+impl<W> JsonEventObserver<W>
+where
+    W: Write,
+{
+    /// Create observer writing events to given writer.
+    pub fn new(writer: W) -> Self {
+        JsonEventObserver {
+            writer: Mutex::new(writer),
+            started: Mutex::new(HashMap::new()),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Return writer, or first error that occurred while writing events.
+    pub fn finish(self) -> io::Result<W> {
+        if let Some(err) = self.error.into_inner().unwrap() {
+            return Err(err);
+        }
+        Ok(self.writer.into_inner().unwrap())
+    }
+
+    fn write_event(&self, event: &JsonEvent) {
+        let mut writer = self.writer.lock().unwrap();
+        let result = serde_json::to_writer(&mut *writer, event)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writer.flush());
+        if let Err(err) = result {
+            self.error.lock().unwrap().get_or_insert(err);
+        }
+    }
+}
+
+impl<W> PackObserver for JsonEventObserver<W>
+where
+    W: Write,
+{
+    fn on_asset_start(&self, name: &str) {
+        self.started
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Instant::now());
+        self.write_event(&JsonEvent {
+            event: "started",
+            asset: name,
+            ms: None,
+            bytes: None,
+        });
+    }
+
+    fn on_asset_built(&self, name: &str, bytes: u64) {
+        let started = self.started.lock().unwrap().remove(name);
+        self.write_event(&JsonEvent {
+            event: "rebuilt",
+            asset: name,
+            ms: started.map(|started| started.elapsed().as_millis()),
+            bytes: Some(bytes),
+        });
+    }
+
+    fn on_asset_skipped(&self, name: &str) {
+        self.started.lock().unwrap().remove(name);
+        self.write_event(&JsonEvent {
+            event: "skipped",
+            asset: name,
+            ms: None,
+            bytes: None,
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{asset_cache::AssetCacheManifestV1, asset_config::AssetConfig};
+
+/// Content encodings of precompressed public asset variants, in order of preference. Variant file path is public asset file path with extension appended.
+pub(crate) const CONTENT_ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// Precompressed variant of public asset file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EncodedVariant {
+    /// Content encoding name as in `Accept-Encoding` header (for example, `br` or `gzip`).
+    pub encoding: String,
+    /// File path relative to target directory.
+    pub path: PathBuf,
+    /// File size in bytes.
+    pub size: u64,
+}
+
+/// Public asset file information for runtime.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeAsset {
+    /// File path relative to target directory.
+    pub path: PathBuf,
+    /// File size in bytes.
+    pub size: u64,
+    /// Available precompressed variants, in order of preference.
+    pub encodings: Vec<EncodedVariant>,
+}
+
+impl RuntimeAsset {
+    /// Return path of best variant for accepted encodings (relative to target directory), or path of uncompressed file if no variant is accepted.
+    pub fn select_path(&self, accepted_encodings: &[&str]) -> (Option<&str>, &Path) {
+        self.encodings
+            .iter()
+            .find(|variant| accepted_encodings.contains(&variant.encoding.as_str()))
+            .map(|variant| (Some(variant.encoding.as_str()), variant.path.as_path()))
+            .unwrap_or((None, self.path.as_path()))
+    }
+}
+
+/// Public asset entry of web manifest written by `write_web_manifest`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WebManifestEntry {
+    /// File path relative to target directory.
+    pub path: PathBuf,
+    /// Subresource Integrity string, if `integrity` is set in config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+}
+
+/// Information about published public assets, for static file handlers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeManifest {
+    pub assets: BTreeMap<String, RuntimeAsset>,
+}
+
+impl RuntimeManifest {
+    /// Build runtime manifest for public assets published to target directory. Assets with missing or unsafe files are skipped.
+    pub fn build(cache_manifest: &AssetCacheManifestV1, config: &AssetConfig) -> io::Result<Self> {
+        let mut assets = BTreeMap::new();
+
+        for (name, entry) in &cache_manifest.map {
+            if !entry.is_public {
+                continue;
+            }
+            let full_path = match cache_manifest.target_full_path(name, config) {
+                Some(full_path) if full_path.is_file() => full_path,
+                _ => continue,
+            };
+
+            let mut encodings = Vec::new();
+            for (encoding, extension) in CONTENT_ENCODINGS.iter() {
+                let variant_path = append_extension(&entry.path, extension);
+                let variant_full_path = append_extension(&full_path, extension);
+                if variant_full_path.is_file() {
+                    encodings.push(EncodedVariant {
+                        encoding: encoding.to_string(),
+                        path: variant_path,
+                        size: fs::metadata(&variant_full_path)?.len(),
+                    });
+                }
+            }
+
+            assets.insert(
+                name.clone(),
+                RuntimeAsset {
+                    path: entry.path.clone(),
+                    size: fs::metadata(&full_path)?.len(),
+                    encodings,
+                },
+            );
+        }
+
+        Ok(RuntimeManifest { assets })
+    }
+
+    /// Load runtime manifest from JSON file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Save runtime manifest to JSON file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// Append extension to file path (`app.js` and `gz` give `app.js.gz`).
+pub(crate) fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
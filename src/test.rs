@@ -3,17 +3,52 @@ mod tests {
     use std::{
         collections::HashMap,
         fs::{copy, create_dir, create_dir_all, remove_dir_all, File},
-        io::{self, Write},
-        path::Path,
+        io::{self, Read, Write},
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::{Duration, SystemTime},
     };
 
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use proptest::prelude::*;
     use tempfile::TempDir;
 
     use crate::{
+        asset_cache::AssetCacheEntry,
+        asset_cache::AssetCacheManifest,
+        asset_cache::CacheDiff,
+        asset_cache::{hash_bytes, hash_file, AssetHash, ParseAssetHashError},
         asset_config::AssetConfig,
-        asset_filter::{AssetFilter, AssetFilterOption, AssetFilterRegistry},
-        assets::{AssetError, AssetErrorType, AssetFilterError},
-        load_cache_manifest, pack,
+        asset_config::Compression,
+        asset_config::HashAlgorithm,
+        asset_config::IntegrityAlgorithm,
+        asset_config::NamedTarget,
+        asset_config::NamingStrategy,
+        asset_config::PublishMode,
+        asset_config::{HashEncoding, HashFormat},
+        asset_filter::{
+            expand_env_vars, get_bytes, get_float, get_integer, get_map, get_safe_path,
+            option_is_flag, require_integer, require_string, AssetFilter, AssetFilterOption,
+            AssetFilterRegistry, AssetFilterStream, FilterContext, StreamFilter,
+        },
+        asset_pack::{PackContext, PackLock, PlannedRebuild},
+        asset_resolver::AssetResolver,
+        assets::{
+            is_safe_relative_path, AssetData, AssetError, AssetErrorType, AssetFilterError,
+            AssetManifest, AssetSource,
+        },
+        clean,
+        clock::MockClock,
+        dependency_graph::DependencyGraph,
+        filters::{ConcatFilter, CopyFilter, JsonMergeFilter},
+        lint::{LintKind, LintSeverity},
+        load_cache_manifest, load_manifest, load_manifests,
+        observer::{JsonEventObserver, NoopObserver, PackObserver},
+        pack, pack_changed, pack_dry_run, pack_manifest, pack_matching, pack_single,
+        pack_with_observer, pack_with_should_rebuild, read_changed_paths, rehydrate_internal,
+        runtime_manifest::RuntimeManifest,
+        runtime_manifest::WebManifestEntry,
+        validate_manifest, write_web_manifest,
     };
 
     #[derive(Debug)]
@@ -21,6 +56,14 @@ mod tests {
 
     impl AssetFilterError for DummyError {}
 
+    impl std::fmt::Display for DummyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "dummy error")
+        }
+    }
+
+    impl std::error::Error for DummyError {}
+
     struct TestCatFilter {}
 
     impl AssetFilter<DummyError> for TestCatFilter {
@@ -55,6 +98,133 @@ mod tests {
         }
     }
 
+    /// Same as `TestCatFilter`, but accepts empty list of inputs.
+    struct TestCatAnyInputsFilter {}
+
+    impl AssetFilter<DummyError> for TestCatAnyInputsFilter {
+        fn process_asset_file(
+            &self,
+            input_file_paths: &[std::path::PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            TestCatFilter {}.process_asset_file(input_file_paths, output_file_path, options)
+        }
+
+        fn accepts_empty_inputs(&self) -> bool {
+            true
+        }
+    }
+
+    /// Filter that writes shared string from filter context.
+    struct TestSharedTextFilter {}
+
+    impl AssetFilter<DummyError> for TestSharedTextFilter {
+        fn process_asset_file(
+            &self,
+            input_file_paths: &[std::path::PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            self.process_asset_file_with_context(
+                input_file_paths,
+                output_file_path,
+                options,
+                &FilterContext::default(),
+            )
+        }
+
+        fn process_asset_file_with_context(
+            &self,
+            _input_file_paths: &[std::path::PathBuf],
+            output_file_path: &Path,
+            _options: &HashMap<String, AssetFilterOption>,
+            context: &FilterContext,
+        ) -> Result<(), AssetError<DummyError>> {
+            let text = context.shared::<String>().cloned().unwrap_or_default();
+            std::fs::write(output_file_path, text)?;
+            Ok(())
+        }
+    }
+
+    /// Same as `TestCatFilter`, but rejects output containing `bad`.
+    struct TestCatValidatedFilter {}
+
+    impl AssetFilter<DummyError> for TestCatValidatedFilter {
+        fn process_asset_file(
+            &self,
+            input_file_paths: &[std::path::PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            TestCatFilter {}.process_asset_file(input_file_paths, output_file_path, options)
+        }
+
+        fn validate_output(
+            &self,
+            output_file_path: &Path,
+            _options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            if std::fs::read_to_string(output_file_path)?.contains("bad") {
+                return Err(AssetError::new(AssetErrorType::FilterError(DummyError {})));
+            }
+            Ok(())
+        }
+    }
+
+    /// Prepare temporary directories with test sources and manifest, return temporary directory, config, manifest path and cache manifest path.
+    fn prepare_test(manifest_name: &str) -> (TempDir, AssetConfig, PathBuf, PathBuf) {
+        let test_directory_path = Path::new("test_files");
+        let temp_directory = TempDir::new().unwrap();
+        let temp_directory_path = temp_directory.path();
+
+        let resource_source_directory_path = test_directory_path.join("source");
+
+        let source_directory_path = temp_directory_path.join("source");
+        let internal_directory_path = temp_directory_path.join("internal");
+        let target_directory_path = temp_directory_path.join("target");
+        let cache_manifest_path = temp_directory_path.join("cache.json");
+        let manifest_path = temp_directory_path.join("assets.json");
+
+        create_dir(&source_directory_path).unwrap();
+        create_dir(&internal_directory_path).unwrap();
+        create_dir(&target_directory_path).unwrap();
+
+        copy(
+            resource_source_directory_path.join("a1.txt"),
+            source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        copy(
+            resource_source_directory_path.join("b.txt"),
+            source_directory_path.join("b.txt"),
+        )
+        .unwrap();
+        copy(test_directory_path.join(manifest_name), &manifest_path).unwrap();
+
+        let config = AssetConfig::new(
+            target_directory_path,
+            internal_directory_path,
+            source_directory_path,
+        );
+
+        (temp_directory, config, manifest_path, cache_manifest_path)
+    }
+
+    fn test_filter_registry() -> AssetFilterRegistry<DummyError> {
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
+
+        AssetFilterRegistry::new(filters_map)
+    }
+
+    fn any_inputs_filter_registry() -> AssetFilterRegistry<DummyError> {
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatAnyInputsFilter {}));
+
+        AssetFilterRegistry::new(filters_map)
+    }
+
     #[test]
     fn test1() {
         let test_directory_path = Path::new("test_files");
@@ -77,7 +247,7 @@ mod tests {
 
         copy(
             resource_source_directory_path.join("a1.txt"),
-            &source_directory_path.join("a.txt"),
+            source_directory_path.join("a.txt"),
         )
         .unwrap();
         copy(
@@ -87,11 +257,11 @@ mod tests {
         .unwrap();
         copy(&resource_manifest_directory_path, &manifest_path).unwrap();
 
-        let config = AssetConfig {
-            target_directory_path: target_directory_path.clone(),
-            internal_directory_path: internal_directory_path.clone(),
-            source_directory_path: source_directory_path.clone(),
-        };
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
 
         let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
         filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
@@ -99,13 +269,14 @@ mod tests {
         let filter_registry = AssetFilterRegistry::new(filters_map);
 
         println!("Run pass #1");
-        pack(
+        let report = pack(
             &manifest_path,
             &cache_manifest_path,
             &config,
             &filter_registry,
         )
         .unwrap();
+        assert_eq!(report.resolution_order, vec!["a", "b", "out"]);
 
         let correct_output1 =
             std::fs::read_to_string(resource_output_directory_path.join("out1.txt")).unwrap();
@@ -179,11 +350,11 @@ mod tests {
         .unwrap();
         copy(&resource_manifest_directory_path, &manifest_path).unwrap();
 
-        let config = AssetConfig {
-            target_directory_path: target_directory_path.clone(),
-            internal_directory_path: internal_directory_path.clone(),
-            source_directory_path: source_directory_path.clone(),
-        };
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
 
         let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
         filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
@@ -274,11 +445,11 @@ mod tests {
         .unwrap();
         copy(&resource_manifest_directory_path, &manifest_path).unwrap();
 
-        let config = AssetConfig {
-            target_directory_path: target_directory_path.clone(),
-            internal_directory_path: internal_directory_path.clone(),
-            source_directory_path: source_directory_path.clone(),
-        };
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
 
         let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
         filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
@@ -368,11 +539,11 @@ mod tests {
         .unwrap();
         copy(&resource_manifest_directory_path, &manifest_path).unwrap();
 
-        let config = AssetConfig {
-            target_directory_path: target_directory_path.clone(),
-            internal_directory_path: internal_directory_path.clone(),
-            source_directory_path: source_directory_path.clone(),
-        };
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
 
         let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
         filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
@@ -397,4 +568,4776 @@ mod tests {
             panic!();
         }
     }
+
+    #[test]
+    fn test_partial_failure() {
+        let test_directory_path = Path::new("test_files");
+        let temp_directory = TempDir::new().unwrap();
+        let temp_directory_path = temp_directory.path();
+
+        let resource_source_directory_path = test_directory_path.join("source");
+        let resource_manifest_directory_path = test_directory_path.join("assets_partial.json");
+
+        let source_directory_path = temp_directory_path.join("source");
+        let internal_directory_path = temp_directory_path.join("internal");
+        let target_directory_path = temp_directory_path.join("target");
+        let cache_manifest_path = temp_directory_path.join("cache.json");
+        let manifest_path = temp_directory_path.join("assets.json");
+
+        create_dir(&source_directory_path).unwrap();
+        create_dir(&internal_directory_path).unwrap();
+        create_dir(&target_directory_path).unwrap();
+
+        copy(
+            resource_source_directory_path.join("a1.txt"),
+            source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        copy(
+            resource_source_directory_path.join("b.txt"),
+            source_directory_path.join("b.txt"),
+        )
+        .unwrap();
+        copy(&resource_manifest_directory_path, &manifest_path).unwrap();
+
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
+
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
+
+        let filter_registry = AssetFilterRegistry::new(filters_map);
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        assert!(result.is_err());
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let entry_a = cache_manifest.get_entry("a").unwrap();
+        assert!(cache_manifest.get_entry("b").is_some());
+        assert!(cache_manifest.get_entry("out").is_none());
+        assert!(target_directory_path.join(&entry_a.path).exists());
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        assert!(result.is_err());
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest.get_entry("a").unwrap(), entry_a);
+    }
+
+    #[test]
+    fn test_pack_matching() {
+        let test_directory_path = Path::new("test_files");
+        let temp_directory = TempDir::new().unwrap();
+        let temp_directory_path = temp_directory.path();
+
+        let resource_source_directory_path = test_directory_path.join("source");
+        let resource_manifest_directory_path = test_directory_path.join("assets.json");
+
+        let source_directory_path = temp_directory_path.join("source");
+        let internal_directory_path = temp_directory_path.join("internal");
+        let target_directory_path = temp_directory_path.join("target");
+        let cache_manifest_path = temp_directory_path.join("cache.json");
+        let manifest_path = temp_directory_path.join("assets.json");
+
+        create_dir(&source_directory_path).unwrap();
+        create_dir(&internal_directory_path).unwrap();
+        create_dir(&target_directory_path).unwrap();
+
+        copy(
+            resource_source_directory_path.join("a1.txt"),
+            source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        copy(
+            resource_source_directory_path.join("b.txt"),
+            source_directory_path.join("b.txt"),
+        )
+        .unwrap();
+        copy(&resource_manifest_directory_path, &manifest_path).unwrap();
+
+        let config = AssetConfig::new(
+            target_directory_path.clone(),
+            internal_directory_path.clone(),
+            source_directory_path.clone(),
+        );
+
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatFilter {}));
+
+        let filter_registry = AssetFilterRegistry::new(filters_map);
+
+        pack_matching(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|name| name != "out",
+        )
+        .unwrap();
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let output_path = cache_manifest.get_entry("out").unwrap().path;
+        assert!(internal_directory_path.join(&output_path).exists());
+        assert!(!target_directory_path.join(&output_path).exists());
+
+        pack_matching(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|name| name == "out",
+        )
+        .unwrap();
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest.get_entry("out").unwrap().path, output_path);
+        assert!(target_directory_path.join(&output_path).exists());
+    }
+
+    #[test]
+    fn test_change_detection() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_change_detection.json");
+        // Source file is edited keeping size and modification time, so fast path is disabled.
+        config.always_hash_sources = true;
+        let filter_registry = test_filter_registry();
+        let source_path = config.source_directory_path.join("a.txt");
+
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&source_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        // Same size and modification time, different content.
+        std::fs::write(&source_path, "A2").unwrap();
+        File::options()
+            .write(true)
+            .open(&source_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let path_changed = |name: &str| {
+            cache_manifest1.get_entry(name).unwrap().path
+                != cache_manifest2.get_entry(name).unwrap().path
+        };
+        assert!(path_changed("a_hash"));
+        assert!(!path_changed("a_mtime"));
+        assert!(path_changed("a_always"));
+        assert!(!path_changed("a_never"));
+
+        File::options()
+            .write(true)
+            .open(&source_path)
+            .unwrap()
+            .set_modified(SystemTime::now())
+            .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        assert_ne!(
+            cache_manifest2.get_entry("a_mtime").unwrap().path,
+            cache_manifest3.get_entry("a_mtime").unwrap().path
+        );
+        assert_eq!(
+            cache_manifest1.get_entry("a_never").unwrap().path,
+            cache_manifest3.get_entry("a_never").unwrap().path
+        );
+    }
+
+    #[test]
+    fn test_cache_diff() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let diff = AssetCacheManifest::default().diff(&cache_manifest1);
+        assert_eq!(diff.added, vec!["a", "b", "out"]);
+        assert!(diff.removed.is_empty());
+        assert!(diff.changed.is_empty());
+
+        let diff = cache_manifest1.diff(&cache_manifest2);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed, vec!["a", "out"]);
+
+        let diff = cache_manifest2.diff(&AssetCacheManifest::default());
+        assert_eq!(diff.removed, vec!["a", "b", "out"]);
+    }
+
+    #[test]
+    fn test_pack_changed() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        std::fs::write(config.source_directory_path.join("b.txt"), "B2").unwrap();
+        let changed_paths = read_changed_paths(io::Cursor::new(format!(
+            "{}\n\n",
+            config.source_directory_path.join("b.txt").display()
+        )))
+        .unwrap();
+
+        let report = pack_changed(
+            &changed_paths,
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["b", "a", "out"]);
+
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest1.diff(&cache_manifest2).changed,
+            vec!["b", "out"]
+        );
+
+        let report = pack_changed(
+            &[PathBuf::from("a.txt")],
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["a", "b", "out"]);
+
+        let report = pack_changed(
+            &[PathBuf::from("c.txt")],
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert!(report.resolution_order.is_empty());
+    }
+
+    #[test]
+    fn test_pack_changed_glob() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_glob.json");
+        let filter_registry = test_filter_registry();
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        assert_eq!(
+            DependencyGraph::new(&manifest).transitive_sources("all"),
+            vec![PathBuf::from("*.txt")]
+        );
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+
+        // New file matching glob pattern.
+        std::fs::write(config.source_directory_path.join("c.txt"), "C").unwrap();
+        let report = pack_changed(
+            &[config.source_directory_path.join("c.txt")],
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["texts", "all"]);
+        assert_eq!(report.rebuilt, vec!["texts", "all"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let all_full_path = cache_manifest.target_full_path("all", &config).unwrap();
+        assert!(std::fs::read_to_string(all_full_path)
+            .unwrap()
+            .contains('C'));
+
+        for path in ["nested/c.txt", "c.js"] {
+            let report = pack_changed(
+                &[PathBuf::from(path)],
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+            assert!(report.resolution_order.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_banner() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_banner.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let style_path = cache_manifest1.get_entry("style").unwrap().path;
+        let style =
+            std::fs::read_to_string(config.target_directory_path.join(&style_path)).unwrap();
+        assert_eq!(style.trim(), "/*! Build 1 */\nA1");
+        let script_path = cache_manifest1.get_entry("script").unwrap().path;
+        let script =
+            std::fs::read_to_string(config.target_directory_path.join(&script_path)).unwrap();
+        assert_eq!(script.trim(), "/*! Build 1 */\nB");
+
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(&manifest_path, manifest.replace("Build 1", "Build 2")).unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let style_path = cache_manifest2.get_entry("style").unwrap().path;
+        let style =
+            std::fs::read_to_string(config.target_directory_path.join(&style_path)).unwrap();
+        assert_eq!(style.trim(), "/*! Build 2 */\nA1");
+        assert_eq!(
+            cache_manifest1.diff(&cache_manifest2).changed,
+            vec!["script", "style"]
+        );
+    }
+
+    #[test]
+    fn test_banner_invalid() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_banner_invalid.json");
+        let filter_registry = test_filter_registry();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+
+        match result.unwrap_err().error_type {
+            AssetErrorType::BannerNotSupportedError(extension) => assert_eq!(extension, "txt"),
+            error_type => panic!("{:?}", error_type),
+        }
+    }
+
+    #[test]
+    fn test_options_change_cascade() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let output1_path = config
+            .target_directory_path
+            .join(cache_manifest1.get_entry("out").unwrap().path);
+        assert_eq!(std::fs::read_to_string(output1_path).unwrap(), "A1Bxy");
+
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(
+            &manifest_path,
+            manifest.replace("\"String\": \"x\"", "\"String\": \"z\""),
+        )
+        .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let output2_path = config
+            .target_directory_path
+            .join(cache_manifest2.get_entry("out").unwrap().path);
+        assert_eq!(std::fs::read_to_string(output2_path).unwrap(), "A1Bzy");
+
+        assert_eq!(
+            cache_manifest1.diff(&cache_manifest2).changed,
+            vec!["mid", "out"]
+        );
+        assert_ne!(
+            cache_manifest1
+                .get_entry("mid")
+                .unwrap()
+                .options_fingerprint,
+            cache_manifest2
+                .get_entry("mid")
+                .unwrap()
+                .options_fingerprint
+        );
+        assert_eq!(
+            cache_manifest1
+                .get_entry("out")
+                .unwrap()
+                .options_fingerprint,
+            cache_manifest2
+                .get_entry("out")
+                .unwrap()
+                .options_fingerprint
+        );
+    }
+
+    #[test]
+    fn test_absolute_source() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets.json");
+        let filter_registry = test_filter_registry();
+
+        let absolute_source_path = std::fs::canonicalize(&config.source_directory_path)
+            .unwrap()
+            .join("a.txt");
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(
+            &manifest_path,
+            manifest.replace(
+                "\"a.txt\"",
+                &serde_json::to_string(&absolute_source_path).unwrap(),
+            ),
+        )
+        .unwrap();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::AssetPathError(path) => assert_eq!(path, absolute_source_path),
+            error_type => panic!("{:?}", error_type),
+        }
+
+        config.allow_absolute_sources = true;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_missing_filters() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_missing_filters.json");
+        let filter_registry = test_filter_registry();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::MissingFiltersError(filter_names) => {
+                assert_eq!(filter_names, vec!["Unknown1", "Unknown2"])
+            }
+            error_type => panic!("{:?}", error_type),
+        }
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest.map.is_empty());
+    }
+
+    #[test]
+    fn test_stable_alias() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_alias.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let entry_a = cache_manifest1.get_entry("a").unwrap();
+        assert_eq!(entry_a.alias_path, Some(PathBuf::from("text/a.txt")));
+        let alias_a_path = config.target_directory_path.join("text").join("a.txt");
+        assert_eq!(std::fs::read_to_string(&alias_a_path).unwrap(), "A1");
+        #[cfg(unix)]
+        assert_eq!(
+            std::fs::read_link(&alias_a_path).unwrap(),
+            Path::new(entry_a.path.file_name().unwrap())
+        );
+
+        let alias_b_path = config.target_directory_path.join("b.txt");
+        assert!(!alias_b_path.symlink_metadata().unwrap().is_symlink());
+        assert_eq!(std::fs::read_to_string(&alias_b_path).unwrap(), "B");
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        assert_ne!(cache_manifest2.get_entry("a").unwrap().path, entry_a.path);
+        assert_eq!(std::fs::read_to_string(&alias_a_path).unwrap(), "A2");
+    }
+
+    /// Normalize path lexically (without accessing file system).
+    fn normalize_path(path: &Path) -> PathBuf {
+        let mut result = PathBuf::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::CurDir => {}
+                std::path::Component::ParentDir => {
+                    result.pop();
+                }
+                component => result.push(component),
+            }
+        }
+        result
+    }
+
+    proptest! {
+        #[test]
+        fn test_output_path_safety(
+            name in r"(\.\.|[a-zA-Z0-9./\\:_-]){0,12}",
+            extension in r"(\.\.|[a-zA-Z0-9./\\:_-]){0,6}",
+            output_base_path in proptest::option::of(r"(//|\.\.|C:|[a-zA-Z0-9./\\:_-]){0,16}"),
+        ) {
+            let data: AssetData = serde_json::from_value(serde_json::json!({
+                "output_base_path": output_base_path,
+                "extension": extension,
+                "source": {"File": "a.txt"},
+            }))
+            .unwrap();
+            let config = AssetConfig::new(PathBuf::new(), PathBuf::new(), PathBuf::new());
+            let output_path =
+                data.output_path(&name, "00000000-0000-0000-0000-000000000000", &config);
+
+            if is_safe_relative_path(&output_path) {
+                let base_path = Path::new("/base/internal");
+                let output_full_path = normalize_path(&base_path.join(&output_path));
+                prop_assert!(output_full_path.starts_with(base_path), "{:?}", output_full_path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_safe_relative_path() {
+        assert!(is_safe_relative_path(Path::new("a/b.txt")));
+        assert!(is_safe_relative_path(Path::new("a/../b.txt")));
+        assert!(is_safe_relative_path(Path::new("./a/./b.txt")));
+        assert!(!is_safe_relative_path(Path::new("../b.txt")));
+        assert!(!is_safe_relative_path(Path::new("a/../../b.txt")));
+        assert!(!is_safe_relative_path(Path::new("/a/b.txt")));
+        #[cfg(windows)]
+        {
+            assert!(!is_safe_relative_path(Path::new("C:a.txt")));
+            assert!(!is_safe_relative_path(Path::new(r"\\server\share\a.txt")));
+            assert!(!is_safe_relative_path(Path::new(r"a\..\..\b.txt")));
+        }
+    }
+
+    #[test]
+    fn test_required_features() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_features.json");
+        let filter_registry = test_filter_registry();
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["b"]);
+
+        config.enabled_features.insert("extra".to_string());
+        config.enabled_features.insert("more".to_string());
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["a", "b", "out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let entry_out = cache_manifest.get_entry("out").unwrap();
+        assert!(config.target_directory_path.join(&entry_out.path).exists());
+
+        config.enabled_features.remove("more");
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest.get_entry("out").is_none());
+        assert!(!config.target_directory_path.join(&entry_out.path).exists());
+        assert!(!config
+            .internal_directory_path
+            .join(&entry_out.path)
+            .exists());
+
+        config.enabled_features.clear();
+        config.enabled_features.insert("more".to_string());
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        std::fs::write(
+            &manifest_path,
+            manifest.replace("\"extra\",\n                \"more\"", "\"more\""),
+        )
+        .unwrap();
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::AssetDisabledError(asset_name) => assert_eq!(asset_name, "a"),
+            error_type => panic!("{:?}", error_type),
+        }
+    }
+
+    #[test]
+    fn test_full_paths() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let mut cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let path = cache_manifest.get_entry("out").unwrap().path;
+        assert_eq!(
+            cache_manifest.internal_full_path("out", &config),
+            Some(config.internal_directory_path.join(&path))
+        );
+        assert_eq!(
+            cache_manifest.target_full_path("out", &config),
+            Some(config.target_directory_path.join(&path))
+        );
+        assert_eq!(cache_manifest.target_full_path("c", &config), None);
+
+        cache_manifest.map.get_mut("out").unwrap().path = PathBuf::from("../out.txt");
+        assert_eq!(cache_manifest.target_full_path("out", &config), None);
+    }
+
+    #[test]
+    fn test_named_targets() {
+        let (temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_alias.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let public_a_path = cache_manifest1.get_public_path("a", None, &config).unwrap();
+        assert_eq!(std::fs::read_to_string(&public_a_path).unwrap(), "A1");
+        assert_eq!(
+            cache_manifest1.get_public_path("a", Some("cdn"), &config),
+            None
+        );
+
+        // Unchanged files in existing target are not copied again.
+        std::fs::write(&public_a_path, "A1 (published)").unwrap();
+
+        let cdn_directory_path = temp_directory.path().join("cdn");
+        config.targets.push(NamedTarget {
+            name: "cdn".to_string(),
+            directory_path: cdn_directory_path.clone(),
+        });
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest2), CacheDiff::default());
+
+        assert_eq!(
+            std::fs::read_to_string(&public_a_path).unwrap(),
+            "A1 (published)"
+        );
+        let cdn_a_path = cache_manifest2
+            .get_public_path("a", Some("cdn"), &config)
+            .unwrap();
+        assert!(cdn_a_path.starts_with(&cdn_directory_path));
+        assert_eq!(std::fs::read_to_string(&cdn_a_path).unwrap(), "A1");
+        assert_eq!(
+            std::fs::read_to_string(cdn_directory_path.join("text").join("a.txt")).unwrap(),
+            "A1"
+        );
+        assert_eq!(
+            std::fs::read_to_string(cdn_directory_path.join("b.txt")).unwrap(),
+            "B"
+        );
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        assert!(!public_a_path.exists());
+        assert!(!cdn_a_path.exists());
+        for target_name in [None, Some("cdn")] {
+            let public_path = cache_manifest3
+                .get_public_path("a", target_name, &config)
+                .unwrap();
+            assert_eq!(std::fs::read_to_string(&public_path).unwrap(), "A2");
+        }
+    }
+
+    #[test]
+    fn test_empty_inputs() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_empty.json");
+        File::create(config.source_directory_path.join("empty.txt")).unwrap();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &test_filter_registry(),
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::NoFilterInputs(name) => assert_eq!(name, "out"),
+            error_type => panic!("{:?}", error_type),
+        }
+
+        let filter_registry = any_inputs_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let out_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert_eq!(std::fs::read_to_string(out_path).unwrap(), "X");
+    }
+
+    #[test]
+    fn test_zero_byte_source() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_empty.json");
+        File::create(config.source_directory_path.join("empty.txt")).unwrap();
+        let filter_registry = any_inputs_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let entry = cache_manifest.get_entry("empty").unwrap();
+        assert_eq!(entry.file_hash.unwrap().hash, *blake3::hash(&[]).as_bytes());
+
+        let target_path = cache_manifest.target_full_path("empty", &config).unwrap();
+        assert_eq!(std::fs::read(target_path).unwrap(), Vec::<u8>::new());
+        let internal_path = cache_manifest.internal_full_path("empty", &config).unwrap();
+        assert_eq!(std::fs::read(internal_path).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_manifest_save() {
+        let (temp_directory, _config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+
+        let saved_manifest_path1 = temp_directory.path().join("saved1.json");
+        manifest.save(&saved_manifest_path1).unwrap();
+        let saved_manifest = load_manifest::<DummyError>(&saved_manifest_path1).unwrap();
+        assert_eq!(saved_manifest, manifest);
+
+        let saved_manifest_path2 = temp_directory.path().join("saved2.json");
+        saved_manifest.save(&saved_manifest_path2).unwrap();
+        let saved_text1 = std::fs::read_to_string(&saved_manifest_path1).unwrap();
+        let saved_text2 = std::fs::read_to_string(&saved_manifest_path2).unwrap();
+        assert_eq!(saved_text1, saved_text2);
+
+        let asset_positions: Vec<usize> = ["\"a\"", "\"b\"", "\"mid\"", "\"out\""]
+            .iter()
+            .map(|key| saved_text1.find(key).unwrap())
+            .collect();
+        assert!(asset_positions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_migrate_naming() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_alias.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let mut cache_manifest2 = cache_manifest1.clone();
+        cache_manifest2
+            .migrate_naming::<DummyError>(&config, NamingStrategy::ContentHash)
+            .unwrap();
+        let path_a = cache_manifest2.get_entry("a").unwrap().path;
+        assert_ne!(path_a, cache_manifest1.get_entry("a").unwrap().path);
+        assert!(!cache_manifest1
+            .internal_full_path("a", &config)
+            .unwrap()
+            .exists());
+        assert!(!cache_manifest1
+            .target_full_path("a", &config)
+            .unwrap()
+            .exists());
+        for full_path in [
+            cache_manifest2.internal_full_path("a", &config).unwrap(),
+            cache_manifest2.target_full_path("a", &config).unwrap(),
+            config.target_directory_path.join("text").join("a.txt"),
+        ] {
+            assert_eq!(std::fs::read_to_string(full_path).unwrap(), "A1");
+        }
+
+        let mut cache_manifest3 = cache_manifest2.clone();
+        cache_manifest3
+            .migrate_naming::<DummyError>(&config, NamingStrategy::ContentHash)
+            .unwrap();
+        assert_eq!(cache_manifest2.diff(&cache_manifest3), CacheDiff::default());
+        assert_eq!(cache_manifest3.public_path("a"), Some(path_a.as_path()));
+
+        cache_manifest3
+            .save::<DummyError>(&cache_manifest_path)
+            .unwrap();
+        config.naming = NamingStrategy::ContentHash;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest4 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest3.diff(&cache_manifest4), CacheDiff::default());
+
+        for source_name in ["a2.txt", "a1.txt"] {
+            copy(
+                Path::new("test_files").join("source").join(source_name),
+                config.source_directory_path.join("a.txt"),
+            )
+            .unwrap();
+            pack(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+        }
+        let cache_manifest5 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest5.get_entry("a").unwrap().path, path_a);
+        assert_eq!(
+            std::fs::read_to_string(cache_manifest5.target_full_path("a", &config).unwrap())
+                .unwrap(),
+            "A1"
+        );
+    }
+
+    #[test]
+    fn test_is_public() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest1.get_entry("out").unwrap().is_public);
+        assert!(!cache_manifest1.get_entry("mid").unwrap().is_public);
+        assert_eq!(
+            cache_manifest1.public_path("out"),
+            Some(cache_manifest1.get_entry("out").unwrap().path.as_path())
+        );
+        assert_eq!(cache_manifest1.public_path("mid"), None);
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets.push("mid".to_string());
+        manifest.save(&manifest_path).unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest2.get_entry("mid").unwrap().is_public);
+        assert_eq!(
+            cache_manifest2.public_path("mid"),
+            Some(cache_manifest2.get_entry("mid").unwrap().path.as_path())
+        );
+        assert_eq!(cache_manifest1.diff(&cache_manifest2), CacheDiff::default());
+    }
+
+    #[test]
+    fn test_runtime_manifest() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_path = cache_manifest.get_entry("out").unwrap().path;
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+
+        let mut br_full_path = out_full_path.into_os_string();
+        br_full_path.push(".br");
+        std::fs::write(&br_full_path, "BR").unwrap();
+
+        let runtime_manifest = RuntimeManifest::build(&cache_manifest, &config).unwrap();
+        assert_eq!(
+            runtime_manifest.assets.keys().collect::<Vec<_>>(),
+            vec!["out"]
+        );
+        let runtime_asset = &runtime_manifest.assets["out"];
+        assert_eq!(runtime_asset.path, out_path);
+        assert_eq!(runtime_asset.size, "A1Bxy".len() as u64);
+        assert_eq!(runtime_asset.encodings.len(), 1);
+        assert_eq!(runtime_asset.encodings[0].encoding, "br");
+        assert_eq!(runtime_asset.encodings[0].size, 2);
+
+        assert_eq!(
+            runtime_asset.select_path(&["gzip", "br"]),
+            (Some("br"), runtime_asset.encodings[0].path.as_path())
+        );
+        assert_eq!(
+            runtime_asset.select_path(&["gzip"]),
+            (None, out_path.as_path())
+        );
+
+        let runtime_manifest_path = temp_directory.path().join("runtime.json");
+        runtime_manifest.save(&runtime_manifest_path).unwrap();
+        assert_eq!(
+            RuntimeManifest::load(&runtime_manifest_path).unwrap(),
+            runtime_manifest
+        );
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let (_temp_directory, config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        let mut cache_manifest = AssetCacheManifest::default();
+
+        let start_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let clock = MockClock::new(start_time);
+
+        let mut context = PackContext::new(&config, &manifest, &filter_registry);
+        context.clock = &clock;
+        cache_manifest
+            .process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)
+            .unwrap();
+        assert_eq!(
+            cache_manifest.get_entry("out").unwrap().built_at,
+            Some(start_time)
+        );
+
+        clock.advance(Duration::from_secs(60));
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+
+        let mut context = PackContext::new(&config, &manifest, &filter_registry);
+        context.clock = &clock;
+        cache_manifest
+            .process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)
+            .unwrap();
+        assert_eq!(
+            cache_manifest.get_entry("a").unwrap().built_at,
+            Some(start_time + Duration::from_secs(60))
+        );
+        assert_eq!(
+            cache_manifest.get_entry("out").unwrap().built_at,
+            Some(start_time + Duration::from_secs(60))
+        );
+        assert_eq!(
+            cache_manifest.get_entry("b").unwrap().built_at,
+            Some(start_time)
+        );
+    }
+
+    #[test]
+    fn test_affected_public_assets() {
+        let (_temp_directory, config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets.insert(0, "b".to_string());
+
+        assert_eq!(
+            manifest.affected_public_assets(Path::new("a.txt"), &config),
+            vec!["out"]
+        );
+        assert_eq!(
+            manifest.affected_public_assets(&config.source_directory_path.join("b.txt"), &config),
+            vec!["b", "out"]
+        );
+        assert!(manifest
+            .affected_public_assets(Path::new("c.txt"), &config)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_manifest_parse_error() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_malformed.json");
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &test_filter_registry(),
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::ManifestParseError { asset, detail } => {
+                assert_eq!(asset.as_deref(), Some("broken"));
+                assert!(!detail.is_empty());
+            }
+            error_type => panic!("{:?}", error_type),
+        }
+
+        std::fs::write(&manifest_path, r#"{"assets": {}}"#).unwrap();
+        match load_manifest::<DummyError>(&manifest_path)
+            .unwrap_err()
+            .error_type
+        {
+            AssetErrorType::ManifestParseError { asset, detail } => {
+                assert_eq!(asset, None);
+                assert!(detail.contains("public_assets"));
+            }
+            error_type => panic!("{:?}", error_type),
+        }
+
+        std::fs::write(&manifest_path, "{").unwrap();
+        assert!(matches!(
+            load_manifest::<DummyError>(&manifest_path)
+                .unwrap_err()
+                .error_type,
+            AssetErrorType::JSONError(_)
+        ));
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let manifest =
+            load_manifest::<DummyError>(&Path::new("test_files").join("assets_chain.json"))
+                .unwrap();
+        let graph = DependencyGraph::new(&manifest);
+
+        assert_eq!(graph.inputs_of("mid"), ["a", "b"]);
+        assert_eq!(graph.dependents_of("a"), ["mid"]);
+        assert!(graph.dependents_of("out").is_empty());
+        assert_eq!(
+            graph.transitive_sources("out"),
+            vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+        );
+        assert_eq!(graph.transitive_sources("b"), vec![PathBuf::from("b.txt")]);
+        assert_eq!(graph.with_dependents(&["b".to_string()]).len(), 3);
+        assert_eq!(graph.find_cycle(), None);
+        assert_eq!(
+            graph.topological_order().unwrap(),
+            vec!["a", "b", "mid", "out"]
+        );
+
+        let cyclic_manifest: AssetManifest = serde_json::from_str(
+            r#"{
+                "assets": {
+                    "x": {"extension": "txt", "source": {"Filtered": {"filter_name": "TestCat", "input_names": ["z"], "options": {}}}},
+                    "y": {"extension": "txt", "source": {"Filtered": {"filter_name": "TestCat", "input_names": ["x"], "options": {}}}},
+                    "z": {"extension": "txt", "source": {"Filtered": {"filter_name": "TestCat", "input_names": ["y"], "options": {}}}}
+                },
+                "public_assets": ["x"]
+            }"#,
+        )
+        .unwrap();
+        let cyclic_graph = DependencyGraph::new(&cyclic_manifest);
+        assert_eq!(
+            cyclic_graph.find_cycle(),
+            Some(vec!["x".to_string(), "z".to_string(), "y".to_string()])
+        );
+        assert!(cyclic_graph.topological_order().is_err());
+    }
+
+    #[test]
+    fn test_json_merge_filter() {
+        let temp_directory = TempDir::new().unwrap();
+        let input1_path = temp_directory.path().join("1.json");
+        let input2_path = temp_directory.path().join("2.json");
+        let output_path = temp_directory.path().join("out").join("out.json");
+        std::fs::write(&input1_path, r#"{"a": {"x": 1, "y": [1]}, "b": "old"}"#).unwrap();
+        std::fs::write(&input2_path, r#"{"a": {"y": [2], "z": 3}, "b": "new"}"#).unwrap();
+        let input_paths = vec![input1_path.clone(), input2_path.clone()];
+
+        let filter = JsonMergeFilter {};
+        let read_output = || -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap()
+        };
+
+        AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            read_output(),
+            serde_json::json!({"a": {"x": 1, "y": [2], "z": 3}, "b": "new"})
+        );
+
+        let mut options = HashMap::new();
+        options.insert(
+            "arrays".to_string(),
+            AssetFilterOption::String("Concat".to_string()),
+        );
+        AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            read_output(),
+            serde_json::json!({"a": {"x": 1, "y": [1, 2], "z": 3}, "b": "new"})
+        );
+
+        std::fs::write(&input2_path, "{").unwrap();
+        let result = AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &HashMap::new(),
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::FilterInputError { path, .. } => assert_eq!(path, input2_path),
+            error_type => panic!("{:?}", error_type),
+        }
+    }
+
+    #[test]
+    fn test_filter_shared_context() {
+        let temp_directory = TempDir::new().unwrap();
+        let output_path = temp_directory.path().join("out.txt");
+
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("Shared".to_string(), Box::new(TestSharedTextFilter {}));
+        let filter_registry = AssetFilterRegistry::new(filters_map)
+            .with_shared_context(Arc::new("shared text".to_string()));
+
+        filter_registry
+            .process_asset_file("Shared".to_string(), &[], &output_path, &HashMap::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "shared text"
+        );
+
+        let filter_context = FilterContext {
+            shared: Some(Arc::new(1u32)),
+        };
+        assert_eq!(filter_context.shared::<u32>(), Some(&1));
+        assert_eq!(filter_context.shared::<String>(), None);
+    }
+
+    #[test]
+    fn test_predict_output_path() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+
+        assert!(matches!(
+            manifest
+                .predict_output_path::<DummyError>("a", &config)
+                .unwrap_err()
+                .error_type,
+            AssetErrorType::OutputPathNotPredictable(_)
+        ));
+
+        config.naming = NamingStrategy::ContentHash;
+        let predicted_a_path = manifest
+            .predict_output_path::<DummyError>("a", &config)
+            .unwrap();
+        match manifest
+            .predict_output_path::<DummyError>("out", &config)
+            .unwrap_err()
+            .error_type
+        {
+            AssetErrorType::OutputPathNotPredictable(name) => assert_eq!(name, "out"),
+            error_type => panic!("{:?}", error_type),
+        }
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &test_filter_registry(),
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest.public_path("a"),
+            Some(predicted_a_path.as_path())
+        );
+    }
+
+    #[test]
+    fn test_rehydrate_internal() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        remove_dir_all(&config.internal_directory_path).unwrap();
+        let not_restored = rehydrate_internal::<DummyError>(&config, &cache_manifest1).unwrap();
+        assert_eq!(not_restored, vec!["out"]);
+        for name in ["a", "b"] {
+            assert!(cache_manifest1
+                .internal_full_path(name, &config)
+                .unwrap()
+                .exists());
+        }
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest2).changed, vec!["out"]);
+    }
+
+    #[test]
+    fn test_asset_log_level() {
+        let mut data: AssetData =
+            serde_json::from_str(r#"{"extension": "txt", "source": {"File": "a.txt"}}"#).unwrap();
+        assert_eq!(data.log_level(), log::Level::Debug);
+        data.debug = true;
+        assert_eq!(data.log_level(), log::Level::Info);
+    }
+
+    #[test]
+    fn test_validate_output() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets.json");
+        std::fs::write(config.source_directory_path.join("a.txt"), "bad").unwrap();
+
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatValidatedFilter {}));
+        let filter_registry = AssetFilterRegistry::new(filters_map);
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        assert!(matches!(
+            result.unwrap_err().error_type,
+            AssetErrorType::FilterError(_)
+        ));
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest.get_entry("out").is_none());
+        let out_directory_path = config.internal_directory_path.join("out_text");
+        assert_eq!(std::fs::read_dir(out_directory_path).unwrap().count(), 0);
+
+        std::fs::write(config.source_directory_path.join("a.txt"), "A").unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_bundle() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_alias.json");
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &test_filter_registry(),
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        for bundle_name in ["bundle.tar", "bundle.tar.gz"] {
+            let bundle_path = temp_directory.path().join(bundle_name);
+            cache_manifest
+                .export_bundle::<DummyError>(&config, &bundle_path)
+                .unwrap();
+
+            let import_directory_path = temp_directory.path().join("import").join(bundle_name);
+            let imported_cache_manifest = AssetCacheManifest::import_bundle::<DummyError>(
+                &bundle_path,
+                &import_directory_path,
+            )
+            .unwrap();
+            assert_eq!(imported_cache_manifest, cache_manifest);
+
+            for name in ["a", "b"] {
+                let public_path = cache_manifest.public_path(name).unwrap();
+                assert_eq!(
+                    std::fs::read(import_directory_path.join(public_path)).unwrap(),
+                    std::fs::read(config.target_directory_path.join(public_path)).unwrap()
+                );
+            }
+            assert_eq!(
+                std::fs::read_to_string(import_directory_path.join("text").join("a.txt")).unwrap(),
+                "A1"
+            );
+        }
+    }
+
+    #[test]
+    fn test_should_rebuild() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        pack_with_should_rebuild(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|entry, _data| (entry.name == "b").then_some(true),
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        // Input rebuilt with identical content does not cause rebuild of filtered asset.
+        assert_eq!(cache_manifest1.diff(&cache_manifest2).changed, vec!["b"]);
+        assert!(cache_manifest2
+            .get_entry("out")
+            .unwrap()
+            .file_hash
+            .is_some());
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        pack_with_should_rebuild(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|_entry, _data| Some(false),
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest2.diff(&cache_manifest3), CacheDiff::default());
+
+        pack_with_should_rebuild(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|_entry, _data| None,
+        )
+        .unwrap();
+        let cache_manifest4 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest3.diff(&cache_manifest4).changed,
+            vec!["a", "out"]
+        );
+    }
+
+    #[test]
+    fn test_bundle_reproducible() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_alias.json");
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &test_filter_registry(),
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let bundle1_path = temp_directory.path().join("bundle1.tar.gz");
+        cache_manifest
+            .export_bundle::<DummyError>(&config, &bundle1_path)
+            .unwrap();
+
+        let b_alias_path = config.target_directory_path.join("b.txt");
+        let file = File::options().write(true).open(&b_alias_path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+
+        let bundle2_path = temp_directory.path().join("bundle2.tar.gz");
+        cache_manifest
+            .export_bundle::<DummyError>(&config, &bundle2_path)
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read(&bundle1_path).unwrap(),
+            std::fs::read(&bundle2_path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+
+        let cache_manifest = AssetCacheManifest::default();
+        let plan = cache_manifest
+            .plan(&config, &manifest, &filter_registry)
+            .unwrap();
+        assert_eq!(plan.names(), vec!["a", "b", "out"]);
+        assert_eq!(plan.reason("out"), Some("asset is not built yet"));
+        assert!(std::fs::read_dir(&config.internal_directory_path)
+            .unwrap()
+            .next()
+            .is_none());
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let mut cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let plan = cache_manifest
+            .plan(&config, &manifest, &filter_registry)
+            .unwrap();
+        assert!(plan.rebuilds.is_empty());
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        let plan = cache_manifest
+            .plan(&config, &manifest, &filter_registry)
+            .unwrap();
+        assert_eq!(
+            plan.rebuilds,
+            vec![
+                PlannedRebuild {
+                    name: "a".to_string(),
+                    reason: "source file hash changed".to_string(),
+                    tentative: false,
+                },
+                PlannedRebuild {
+                    name: "out".to_string(),
+                    reason: "input assets were rebuilt".to_string(),
+                    tentative: true,
+                },
+            ]
+        );
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest), CacheDiff::default());
+
+        cache_manifest
+            .apply(&plan, &config, &manifest, &filter_registry)
+            .unwrap();
+        assert_eq!(
+            cache_manifest1.diff(&cache_manifest).changed,
+            vec!["a", "out"]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_outputs() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_duplicate.json");
+        let filter_registry = test_filter_registry();
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(
+            report.duplicate_outputs,
+            vec![vec!["a", "a_copy"], vec!["out1", "out2"]]
+        );
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.duplicate_outputs.len(), 2);
+    }
+
+    #[test]
+    fn test_copy_public_to() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_banner.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let style_full_path = cache_manifest.target_full_path("style", &config).unwrap();
+        let style = std::fs::read(&style_full_path).unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        let written = cache_manifest
+            .copy_public_to::<_, DummyError>("style", &config, &mut output)
+            .unwrap();
+        assert_eq!(output, style);
+        assert_eq!(written, style.len() as u64);
+
+        std::fs::remove_file(&style_full_path).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let written = cache_manifest
+            .copy_public_to::<_, DummyError>("style", &config, &mut output)
+            .unwrap();
+        assert_eq!(output, style);
+        assert_eq!(written, style.len() as u64);
+
+        let result =
+            cache_manifest.copy_public_to::<_, DummyError>("missing", &config, &mut output);
+        assert!(matches!(
+            result.unwrap_err().error_type,
+            AssetErrorType::AssetNotFoundInManifestError(_)
+        ));
+    }
+
+    #[test]
+    fn test_max_dependency_depth() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+        assert_eq!(config.max_dependency_depth, 64);
+
+        config.max_dependency_depth = 2;
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::DependencyTooDeep { asset, depth } => {
+                assert_eq!(asset, "a");
+                assert_eq!(depth, 3);
+            }
+            error_type => panic!("unexpected error {:?}", error_type),
+        }
+
+        config.max_dependency_depth = 3;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_error_display() {
+        let err = AssetError::<DummyError>::new(AssetErrorType::NoFilterInputs("out".to_string()));
+        assert_eq!(err.to_string(), "asset out has no filter inputs");
+        assert!(std::error::Error::source(&err).is_none());
+
+        let err = AssetError::<DummyError>::new(AssetErrorType::AssetSourceNotFound(
+            PathBuf::from("a.txt"),
+        ));
+        assert_eq!(err.to_string(), "source file \"a.txt\" is not found");
+
+        let err = AssetError::from(DummyError {});
+        assert_eq!(err.to_string(), "filter error");
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "dummy error"
+        );
+
+        let err: Box<dyn std::error::Error> = Box::new(AssetError::<DummyError>::from(
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no file"),
+        ));
+        assert!(err
+            .source()
+            .unwrap()
+            .downcast_ref::<std::io::Error>()
+            .is_some());
+    }
+
+    #[test]
+    fn test_anyhow_error() {
+        let err: anyhow::Error = AssetError::<DummyError>::from(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no file",
+        ))
+        .into();
+        assert_eq!(err.to_string(), "I/O error");
+        assert_eq!(err.chain().count(), 2);
+        assert!(err.root_cause().downcast_ref::<std::io::Error>().is_some());
+
+        let err: anyhow::Error = AssetError::from(DummyError {}).into();
+        assert_eq!(format!("{:#}", err), "filter error: dummy error");
+
+        let err: anyhow::Error = AssetError::<DummyError>::new(
+            AssetErrorType::AssetNotFoundInManifestError("style".to_string()),
+        )
+        .into();
+        assert_eq!(err.to_string(), "asset style is not found in manifest");
+    }
+
+    #[test]
+    fn test_hash_format() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.naming = NamingStrategy::ContentHash;
+        config.hash_format = HashFormat {
+            length: Some(8),
+            encoding: HashEncoding::Hex,
+        };
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.assets.get_mut("b").unwrap().hash_format = Some(HashFormat {
+            length: None,
+            encoding: HashEncoding::Base64Url,
+        });
+        manifest.save(&manifest_path).unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        let entry_a = cache_manifest.get_entry("a").unwrap();
+        let a_bytes = std::fs::read(config.source_directory_path.join("a.txt")).unwrap();
+        let a_hash = blake3::hash(&a_bytes);
+        assert_eq!(entry_a.output_hash.unwrap().hash, *a_hash.as_bytes());
+        assert_eq!(
+            entry_a.path,
+            PathBuf::from(format!("a-{}.txt", &a_hash.to_hex()[..8]))
+        );
+
+        let entry_b = cache_manifest.get_entry("b").unwrap();
+        let b_suffix = entry_b
+            .path
+            .to_str()
+            .unwrap()
+            .strip_prefix("b-")
+            .unwrap()
+            .strip_suffix(".txt")
+            .unwrap()
+            .to_string();
+        assert_eq!(b_suffix.len(), 43);
+        assert!(!b_suffix.contains('='));
+    }
+
+    #[test]
+    fn test_hash_collision() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.naming = NamingStrategy::ContentHash;
+        config.hash_format = HashFormat {
+            length: Some(0),
+            encoding: HashEncoding::Hex,
+        };
+        std::fs::write(config.internal_directory_path.join("a-.txt"), "other").unwrap();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::OutputHashCollision { asset, path } => {
+                assert_eq!(asset, "a");
+                assert_eq!(path, PathBuf::from("a-.txt"));
+            }
+            error_type => panic!("unexpected error {:?}", error_type),
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_run_filter() {
+        use crate::{filters::JsonMergeFilter, testing::run_filter};
+
+        let output = run_filter::<_, DummyError>(
+            &JsonMergeFilter {},
+            &[br#"{"a": [1], "b": 1}"#, br#"{"a": [2]}"#],
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(output, br#"{"a":[2],"b":1}"#);
+
+        let output = run_filter::<_, DummyError>(
+            &TestCatFilter {},
+            &[b"A", b"B"],
+            &HashMap::from([(
+                "additional_text".to_string(),
+                AssetFilterOption::String("C".to_string()),
+            )]),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().trim(), "ABC");
+
+        let result =
+            run_filter::<_, DummyError>(&TestCatValidatedFilter {}, &[b"bad"], &HashMap::new());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_auto_prune() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.auto_prune = true;
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert!(report.pruned.is_empty());
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_path = cache_manifest1.get_entry("out").unwrap().path;
+
+        let stray_path = config.target_directory_path.join("stray.txt");
+        std::fs::write(&stray_path, "stray").unwrap();
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets.push("missing".to_string());
+        manifest.save(&manifest_path).unwrap();
+
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        assert!(result.is_err());
+        assert!(stray_path.exists());
+
+        manifest.public_assets = vec!["a".to_string(), "b".to_string()];
+        manifest.save(&manifest_path).unwrap();
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let mut expected = vec![
+            config.internal_directory_path.join(&out_path),
+            config.target_directory_path.join(&out_path),
+            stray_path.clone(),
+        ];
+        expected.sort();
+        assert_eq!(report.pruned, expected);
+        assert!(!stray_path.exists());
+
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest2.get_entry("out").is_none());
+        assert!(cache_manifest2
+            .target_full_path("a", &config)
+            .unwrap()
+            .exists());
+    }
+
+    #[test]
+    fn test_glob_source() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_glob.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest1.get_entry("texts").unwrap().files,
+            vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+        );
+        let all_full_path = cache_manifest1.target_full_path("all", &config).unwrap();
+        assert_eq!(std::fs::read_to_string(all_full_path).unwrap(), "A1B");
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest2), CacheDiff::default());
+
+        std::fs::write(config.source_directory_path.join("c.txt"), "C").unwrap();
+        std::fs::remove_file(config.source_directory_path.join("a.txt")).unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest2.diff(&cache_manifest3).changed,
+            vec!["all", "texts"]
+        );
+        let all_full_path = cache_manifest3.target_full_path("all", &config).unwrap();
+        assert_eq!(std::fs::read_to_string(all_full_path).unwrap(), "BC");
+        assert!(!cache_manifest2
+            .internal_full_path("texts", &config)
+            .unwrap()
+            .exists());
+
+        // Public glob asset is rejected before other public assets are built.
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets.push("texts".to_string());
+        manifest.save(&manifest_path).unwrap();
+        std::fs::write(config.source_directory_path.join("b.txt"), "B2").unwrap();
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::PublicGlobAsset(name) => assert_eq!(name, "texts"),
+            error_type => panic!("unexpected error {:?}", error_type),
+        }
+        let cache_manifest4 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest3.diff(&cache_manifest4), CacheDiff::default());
+        assert_eq!(
+            validate_manifest(&manifest, &filter_registry)
+                .unwrap_err()
+                .iter()
+                .map(|error_type| error_type.to_string())
+                .collect::<Vec<_>>(),
+            vec!["glob asset texts can not be public"]
+        );
+    }
+
+    #[test]
+    fn test_glob_outside_source_directory() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_glob.json");
+        let filter_registry = test_filter_registry();
+
+        for pattern in ["../*.txt", "nested/../../*.txt", "/*.txt"] {
+            let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+            manifest.assets.get_mut("texts").unwrap().source =
+                AssetSource::Glob(pattern.to_string());
+            let result = pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry);
+            match result.unwrap_err().error_type {
+                AssetErrorType::AssetPathError(path) => assert_eq!(path, PathBuf::from(pattern)),
+                error_type => panic!("unexpected error {:?}", error_type),
+            }
+        }
+    }
+
+    #[test]
+    fn test_generation() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_partial.json");
+        let filter_registry = test_filter_registry();
+
+        assert_eq!(AssetCacheManifest::default().generation(), 0);
+        let result = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        );
+        assert!(result.is_err());
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest.generation(), 0);
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets = vec!["a".to_string()];
+        manifest.save(&manifest_path).unwrap();
+        for generation in 1..=2 {
+            pack(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+            let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+            assert_eq!(cache_manifest.generation(), generation);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.assets.get_mut("a").unwrap().mode = Some(0o755);
+        manifest.save(&manifest_path).unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let a_full_path = cache_manifest.target_full_path("a", &config).unwrap();
+        let a_mode = std::fs::metadata(&a_full_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(a_mode & 0o777, 0o755);
+
+        manifest.assets.get_mut("a").unwrap().mode = Some(0o600);
+        manifest.save(&manifest_path).unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let a_full_path = cache_manifest.target_full_path("a", &config).unwrap();
+        let a_mode = std::fs::metadata(&a_full_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(a_mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_json_event_observer() {
+        let (_temp_directory, config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        let mut cache_manifest = AssetCacheManifest::default();
+
+        let read_events = |output: Vec<u8>| -> Vec<(String, String)> {
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let event: serde_json::Value = serde_json::from_str(line).unwrap();
+                    if event["event"] == "rebuilt" {
+                        assert!(event["ms"].is_u64());
+                        assert!(event["bytes"].is_u64());
+                    }
+                    (
+                        event["event"].as_str().unwrap().to_string(),
+                        event["asset"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect()
+        };
+
+        let observer = JsonEventObserver::new(Vec::new());
+        let mut context = PackContext::new(&config, &manifest, &filter_registry);
+        context.observer = Some(&observer);
+        cache_manifest
+            .process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)
+            .unwrap();
+        let events = read_events(observer.finish().unwrap());
+        let expected: Vec<(String, String)> = [
+            ("started", "a"),
+            ("rebuilt", "a"),
+            ("started", "b"),
+            ("rebuilt", "b"),
+            ("started", "out"),
+            ("rebuilt", "out"),
+        ]
+        .iter()
+        .map(|(event, asset)| (event.to_string(), asset.to_string()))
+        .collect();
+        assert_eq!(events, expected);
+
+        let observer = JsonEventObserver::new(Vec::new());
+        let mut context = PackContext::new(&config, &manifest, &filter_registry);
+        context.observer = Some(&observer);
+        cache_manifest
+            .process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)
+            .unwrap();
+        let events = read_events(observer.finish().unwrap());
+        assert_eq!(events.len(), 6);
+        assert!(events.iter().all(|(event, _)| event != "rebuilt"));
+    }
+
+    #[test]
+    fn test_shared_output() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_shared.json");
+        let filter_registry = test_filter_registry();
+        config.naming = NamingStrategy::ContentHash;
+        config.auto_prune = true;
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let shared_path = cache_manifest1.get_entry("x").unwrap().path;
+        assert_eq!(
+            cache_manifest1.get_entry("dir/x").unwrap().path,
+            shared_path
+        );
+        assert_eq!(cache_manifest1.path_references(&shared_path), 2);
+
+        copy(
+            Path::new("test_files").join("source").join("a2.txt"),
+            config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let new_shared_path = cache_manifest2.get_entry("x").unwrap().path;
+        assert_ne!(new_shared_path, shared_path);
+        assert!(!config.internal_directory_path.join(&shared_path).exists());
+        assert!(!config.target_directory_path.join(&shared_path).exists());
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.assets.remove("dir/x");
+        manifest.public_assets = vec!["x".to_string()];
+        manifest.save(&manifest_path).unwrap();
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert!(report.pruned.is_empty());
+        assert!(config.target_directory_path.join(&new_shared_path).exists());
+    }
+
+    #[test]
+    fn test_asset_hash_legacy_format() {
+        let hash = blake3::hash(b"test");
+        let hash_base64: AssetHash = serde_json::from_value(
+            serde_json::to_value(AssetHash {
+                algorithm: HashAlgorithm::Blake3,
+                hash: hash.as_bytes().to_vec(),
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        let hash_bytes: AssetHash =
+            serde_json::from_value(serde_json::json!(hash.as_bytes().to_vec())).unwrap();
+        assert_eq!(hash_base64.hash, *hash.as_bytes());
+        assert_eq!(hash_bytes, hash_base64);
+
+        assert!(serde_json::from_value::<AssetHash>(serde_json::json!([1, 2, 3])).is_err());
+        assert!(serde_json::from_value::<AssetHash>(serde_json::json!(vec![0; 33])).is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        let (_temp_directory, config, _manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        let manifest = AssetManifest::from_json_value::<DummyError>(serde_json::json!({
+            "assets": {
+                "a": {"extension": "txt", "source": {"File": "/a.txt"}},
+                "b": {"extension": "bin", "source": {"File": "b.txt"}, "banner": "test"},
+                "c": {"output_base_path": "../c", "extension": "txt", "source": {"File": "c.txt"}},
+                "d": {"extension": "txt", "source": {"Glob": "*.txt"}},
+                "x": {"extension": "txt", "source": {"Filtered": {"filter_name": "TestCat", "input_names": ["y", "missing"], "options": {}}}},
+                "y": {"extension": "txt", "source": {"Filtered": {"filter_name": "Unknown", "input_names": ["x"], "options": {}}}},
+                "z": {"extension": "txt", "source": {"File": "z.txt"}, "hash_format": {"length": 0, "encoding": "Hex"}}
+            },
+            "public_assets": ["a", "b", "d", "x", "undefined", "z"]
+        }))
+        .unwrap();
+
+        let findings: Vec<(String, LintKind)> = manifest
+            .lint(&config, &filter_registry)
+            .into_iter()
+            .map(|finding| (finding.asset, finding.kind))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    "a".to_string(),
+                    LintKind::AbsoluteSource(PathBuf::from("/a.txt"))
+                ),
+                (
+                    "b".to_string(),
+                    LintKind::BannerNotSupported("bin".to_string())
+                ),
+                (
+                    "c".to_string(),
+                    LintKind::UnsafeOutputPath(PathBuf::from("../c/c-0.txt"))
+                ),
+                ("c".to_string(), LintKind::UnreachableAsset),
+                ("d".to_string(), LintKind::PublicGlob),
+                ("undefined".to_string(), LintKind::MissingPublicAsset),
+                (
+                    "x".to_string(),
+                    LintKind::MissingInput("missing".to_string())
+                ),
+                (
+                    "x".to_string(),
+                    LintKind::DependencyCycle(vec!["x".to_string(), "y".to_string()])
+                ),
+                (
+                    "y".to_string(),
+                    LintKind::MissingFilter("Unknown".to_string())
+                ),
+                (
+                    "z".to_string(),
+                    LintKind::InvalidHashFormat(HashFormat {
+                        length: Some(0),
+                        encoding: HashEncoding::Hex
+                    })
+                ),
+            ]
+        );
+        assert_eq!(LintKind::UnreachableAsset.severity(), LintSeverity::Warning);
+        assert_eq!(LintKind::PublicGlob.severity(), LintSeverity::Error);
+
+        let manifest =
+            load_manifest::<DummyError>(&Path::new("test_files").join("assets_public.json"))
+                .unwrap();
+        assert!(manifest.lint(&config, &filter_registry).is_empty());
+    }
+
+    #[test]
+    fn test_integer_option() {
+        let options: HashMap<String, AssetFilterOption> =
+            serde_json::from_str(r#"{"quality": {"Integer": -42}, "name": {"String": "x"}}"#)
+                .unwrap();
+        assert_eq!(options["quality"], AssetFilterOption::Integer(-42));
+        assert_eq!(get_integer(options["quality"].clone()), Some(-42));
+        assert_eq!(get_integer(options["name"].clone()), None);
+        assert_eq!(
+            serde_json::to_value(&options["quality"]).unwrap(),
+            serde_json::json!({"Integer": -42})
+        );
+
+        assert!(serde_json::from_str::<AssetFilterOption>(r#"{"Integer": 1.5}"#).is_err());
+    }
+
+    #[test]
+    fn test_float_option() {
+        let options: HashMap<String, AssetFilterOption> =
+            serde_json::from_str(r#"{"gamma": {"Float": 2.2}, "flag": "Flag"}"#).unwrap();
+        assert_eq!(options["gamma"], AssetFilterOption::Float(2.2));
+        assert_eq!(get_float(options["gamma"].clone()), Some(2.2));
+        assert_eq!(get_float(options["flag"].clone()), None);
+        assert_eq!(option_is_flag(Some(options["gamma"].clone())), None);
+        assert_eq!(option_is_flag(Some(options["flag"].clone())), Some(true));
+
+        let value = serde_json::to_value(&options).unwrap();
+        let options_deserialized: HashMap<String, AssetFilterOption> =
+            serde_json::from_value(value).unwrap();
+        assert_eq!(options_deserialized, options);
+
+        assert_eq!(
+            AssetFilterOption::Float(f64::NAN),
+            AssetFilterOption::Float(f64::NAN)
+        );
+        assert_ne!(AssetFilterOption::Float(1.0), AssetFilterOption::Integer(1));
+    }
+
+    #[test]
+    fn test_map_option() {
+        let option: AssetFilterOption = serde_json::from_str(
+            r#"{"Map": {"small": {"Map": {"width": {"Integer": 320}, "retina": "Flag"}}, "large": {"Map": {"width": {"Integer": 1280}}}}}"#,
+        )
+        .unwrap();
+        let breakpoints = get_map(option.clone()).unwrap();
+        let small = get_map(breakpoints["small"].clone()).unwrap();
+        assert_eq!(small["width"], AssetFilterOption::Integer(320));
+        assert_eq!(small["retina"], AssetFilterOption::Flag);
+        assert_eq!(get_map(AssetFilterOption::Flag), None);
+
+        let serialized = serde_json::to_string(&option).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"Map":{"large":{"Map":{"width":{"Integer":1280}}},"small":{"Map":{"retina":"Flag","width":{"Integer":320}}}}}"#
+        );
+        let option_deserialized: AssetFilterOption = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(option_deserialized, option);
+
+        let mut option_changed = option.clone();
+        if let AssetFilterOption::Map(breakpoints) = &mut option_changed {
+            if let Some(AssetFilterOption::Map(small)) = breakpoints.get_mut("small") {
+                small.insert("width".to_string(), AssetFilterOption::Integer(360));
+            }
+        }
+        assert_ne!(option_changed, option);
+    }
+
+    #[test]
+    fn test_require_option() {
+        let options: HashMap<String, AssetFilterOption> = HashMap::from([
+            (
+                "name".to_string(),
+                AssetFilterOption::String("x".to_string()),
+            ),
+            ("width".to_string(), AssetFilterOption::Integer(320)),
+        ]);
+        assert_eq!(require_string::<DummyError>(&options, "name").unwrap(), "x");
+        assert_eq!(
+            require_integer::<DummyError>(&options, "width").unwrap(),
+            320
+        );
+
+        for key in ["missing", "width"] {
+            match require_string::<DummyError>(&options, key)
+                .unwrap_err()
+                .error_type
+            {
+                AssetErrorType::MissingFilterOption(option) => assert_eq!(option, key),
+                error_type => panic!("unexpected error {:?}", error_type),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes_option() {
+        let option: AssetFilterOption = serde_json::from_str(r#"{"Bytes": "AAEC/w=="}"#).unwrap();
+        assert_eq!(get_bytes(option.clone()), Some(vec![0, 1, 2, 255]));
+        assert_eq!(get_bytes(AssetFilterOption::Integer(1)), None);
+        assert_eq!(
+            serde_json::to_value(&option).unwrap(),
+            serde_json::json!({"Bytes": "AAEC/w=="})
+        );
+
+        assert!(serde_json::from_str::<AssetFilterOption>(r#"{"Bytes": "not base64!"}"#).is_err());
+        assert!(serde_json::from_str::<AssetFilterOption>(r#"{"Bytes": [1, 2]}"#).is_err());
+    }
+
+    #[test]
+    fn test_path_option() {
+        let base = Path::new("source");
+        let option: AssetFilterOption =
+            serde_json::from_str(r#"{"Path": "config/./filter.json"}"#).unwrap();
+        assert_eq!(
+            get_safe_path::<DummyError>(option, base).unwrap(),
+            Some(base.join("config/./filter.json"))
+        );
+        assert_eq!(
+            get_safe_path::<DummyError>(AssetFilterOption::Flag, base).unwrap(),
+            None
+        );
+
+        for path in ["/etc/passwd", "../secret", "config/../../secret"] {
+            let result =
+                get_safe_path::<DummyError>(AssetFilterOption::Path(PathBuf::from(path)), base);
+            assert!(matches!(
+                result.unwrap_err().error_type,
+                AssetErrorType::AssetPathError(_)
+            ));
+        }
+    }
+
+    struct TestUppercaseStreamFilter {}
+
+    impl AssetFilterStream<DummyError> for TestUppercaseStreamFilter {
+        fn process_asset_stream(
+            &self,
+            inputs: &mut [Box<dyn io::Read>],
+            output: &mut dyn Write,
+            _options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            for input in inputs {
+                let mut text = String::new();
+                input.read_to_string(&mut text)?;
+                output.write_all(text.to_uppercase().as_bytes())?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_filter() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let mut filters_map: HashMap<String, Box<dyn AssetFilter<DummyError>>> = HashMap::new();
+        filters_map.insert(
+            "TestCat".to_string(),
+            Box::new(StreamFilter(TestUppercaseStreamFilter {})),
+        );
+        let filter_registry = AssetFilterRegistry::new(filters_map);
+        std::fs::write(config.source_directory_path.join("a.txt"), "a").unwrap();
+        std::fs::write(config.source_directory_path.join("b.txt"), "b").unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert_eq!(std::fs::read_to_string(out_full_path).unwrap(), "AB");
+
+        let mut inputs: Vec<Box<dyn io::Read>> = vec![Box::new(&b"x"[..]), Box::new(&b"y"[..])];
+        let mut output: Vec<u8> = Vec::new();
+        TestCatFilter {}
+            .process_asset_stream(&mut inputs, &mut output, &HashMap::new())
+            .unwrap();
+        assert_eq!(output, b"xy");
+    }
+
+    #[test]
+    fn test_filter_max_concurrency() {
+        let filter_registry = test_filter_registry();
+        assert_eq!(filter_registry.max_concurrency("TestCat"), None);
+        assert_eq!(filter_registry.max_concurrency("Missing"), None);
+    }
+
+    #[cfg(feature = "async")]
+    struct TestCatFilterAsync {}
+
+    #[cfg(feature = "async")]
+    #[async_trait::async_trait]
+    impl crate::asset_filter_async::AssetFilterAsync<DummyError> for TestCatFilterAsync {
+        async fn process_asset_file(
+            &self,
+            input_file_paths: &[PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            TestCatFilter {}.process_asset_file(input_file_paths, output_file_path, options)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    struct TestLimitedFilterAsync {
+        /// Number of running instances and maximum number of instances observed.
+        running: Arc<std::sync::Mutex<(usize, usize)>>,
+    }
+
+    #[cfg(feature = "async")]
+    #[async_trait::async_trait]
+    impl crate::asset_filter_async::AssetFilterAsync<DummyError> for TestLimitedFilterAsync {
+        async fn process_asset_file(
+            &self,
+            input_file_paths: &[PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            {
+                let mut running = self.running.lock().unwrap();
+                running.0 += 1;
+                running.1 = running.1.max(running.0);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            let result =
+                TestCatFilter {}.process_asset_file(input_file_paths, output_file_path, options);
+            self.running.lock().unwrap().0 -= 1;
+            result
+        }
+
+        fn max_concurrency(&self) -> Option<usize> {
+            Some(1)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_filter_max_concurrency() {
+        use crate::{asset_filter_async::AssetFilterRegistryAsync, pack_async};
+
+        let mut filters_map: HashMap<
+            String,
+            Box<dyn crate::asset_filter_async::AssetFilterAsync<DummyError>>,
+        > = HashMap::new();
+        let running = Arc::new(std::sync::Mutex::new((0, 0)));
+        filters_map.insert(
+            "TestCat".to_string(),
+            Box::new(TestLimitedFilterAsync {
+                running: running.clone(),
+            }),
+        );
+        let filter_registry = AssetFilterRegistryAsync::new(filters_map);
+        assert_eq!(filter_registry.max_concurrency("TestCat"), Some(1));
+        assert_eq!(filter_registry.max_concurrency("Missing"), None);
+
+        // Pack runs sharing registry run filter one at a time.
+        let tests: Vec<_> = (0..3).map(|_| prepare_test("assets_public.json")).collect();
+        std::thread::scope(|scope| {
+            for (_temp_directory, config, manifest_path, cache_manifest_path) in &tests {
+                let filter_registry = &filter_registry;
+                scope.spawn(move || {
+                    pollster::block_on(pack_async(
+                        manifest_path,
+                        cache_manifest_path,
+                        config,
+                        filter_registry,
+                    ))
+                    .unwrap();
+                });
+            }
+        });
+
+        assert_eq!(*running.lock().unwrap(), (0, 1));
+        for (_temp_directory, config, _manifest_path, cache_manifest_path) in &tests {
+            let cache_manifest = load_cache_manifest::<DummyError>(cache_manifest_path).unwrap();
+            let out_full_path = cache_manifest.target_full_path("out", config).unwrap();
+            assert!(std::fs::read_to_string(out_full_path)
+                .unwrap()
+                .ends_with("test"));
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_pack_async() {
+        use crate::{asset_filter_async::AssetFilterRegistryAsync, pack_async};
+
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let mut filters_map: HashMap<
+            String,
+            Box<dyn crate::asset_filter_async::AssetFilterAsync<DummyError>>,
+        > = HashMap::new();
+        filters_map.insert("TestCat".to_string(), Box::new(TestCatFilterAsync {}));
+        let filter_registry = AssetFilterRegistryAsync::new(filters_map);
+
+        let report = pollster::block_on(pack_async(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        ))
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["a", "b", "out"]);
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        let mut expected =
+            std::fs::read_to_string(config.source_directory_path.join("a.txt")).unwrap();
+        expected.push_str(
+            &std::fs::read_to_string(config.source_directory_path.join("b.txt")).unwrap(),
+        );
+        expected.push_str("test");
+        assert_eq!(std::fs::read_to_string(out_full_path).unwrap(), expected);
+
+        // Up to date assets are not rebuilt.
+        let report = pollster::block_on(pack_async(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        ))
+        .unwrap();
+        assert_eq!(report.resolution_order, vec!["a", "b", "out"]);
+        assert_eq!(
+            load_cache_manifest::<DummyError>(&cache_manifest_path)
+                .unwrap()
+                .get_entry("out"),
+            cache_manifest.get_entry("out")
+        );
+        let internal_names: Vec<String> = std::fs::read_dir(&config.internal_directory_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(internal_names
+            .iter()
+            .all(|name| !name.starts_with(".prepared-")));
+    }
+
+    #[cfg(feature = "async")]
+    struct TestCountingFilterAsync {
+        /// Number of filter runs.
+        runs: Arc<std::sync::atomic::AtomicUsize>,
+        /// Text written to output instead of inputs, if set.
+        constant: Option<&'static str>,
+    }
+
+    #[cfg(feature = "async")]
+    #[async_trait::async_trait]
+    impl crate::asset_filter_async::AssetFilterAsync<DummyError> for TestCountingFilterAsync {
+        async fn process_asset_file(
+            &self,
+            input_file_paths: &[PathBuf],
+            output_file_path: &Path,
+            options: &HashMap<String, AssetFilterOption>,
+        ) -> Result<(), AssetError<DummyError>> {
+            self.runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            match self.constant {
+                Some(constant) => Ok(std::fs::write(output_file_path, constant)?),
+                None => {
+                    TestCatFilter {}.process_asset_file(input_file_paths, output_file_path, options)
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_pack_async_unchanged_inputs() {
+        use crate::{asset_filter_async::AssetFilterRegistryAsync, pack_async};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        std::fs::write(
+            &manifest_path,
+            r#"{
+                "assets": {
+                    "a": {"extension": "txt", "source": {"File": "a.txt"}},
+                    "mid": {
+                        "extension": "txt",
+                        "source": {"Filtered": {"filter_name": "Const", "input_names": ["a"], "options": {}}}
+                    },
+                    "out": {
+                        "extension": "txt",
+                        "source": {"Filtered": {"filter_name": "TestCat", "input_names": ["mid"], "options": {}}}
+                    }
+                },
+                "public_assets": ["out"]
+            }"#,
+        )
+        .unwrap();
+        let const_runs = Arc::new(AtomicUsize::new(0));
+        let cat_runs = Arc::new(AtomicUsize::new(0));
+        let mut filters_map: HashMap<
+            String,
+            Box<dyn crate::asset_filter_async::AssetFilterAsync<DummyError>>,
+        > = HashMap::new();
+        filters_map.insert(
+            "Const".to_string(),
+            Box::new(TestCountingFilterAsync {
+                runs: const_runs.clone(),
+                constant: Some("C"),
+            }),
+        );
+        filters_map.insert(
+            "TestCat".to_string(),
+            Box::new(TestCountingFilterAsync {
+                runs: cat_runs.clone(),
+                constant: None,
+            }),
+        );
+        let filter_registry = AssetFilterRegistryAsync::new(filters_map);
+
+        pollster::block_on(pack_async(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        ))
+        .unwrap();
+        assert_eq!(const_runs.load(Ordering::SeqCst), 1);
+        assert_eq!(cat_runs.load(Ordering::SeqCst), 1);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        // Asset whose rebuilt input has same content is not rebuilt.
+        std::fs::write(config.source_directory_path.join("a.txt"), "changed").unwrap();
+        pollster::block_on(pack_async(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        ))
+        .unwrap();
+        assert_eq!(const_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(cat_runs.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            load_cache_manifest::<DummyError>(&cache_manifest_path)
+                .unwrap()
+                .get_entry("out"),
+            cache_manifest.get_entry("out")
+        );
+    }
+
+    #[test]
+    fn test_source_metadata_fast_path() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let entry = cache_manifest1.get_entry("a").unwrap();
+        assert_eq!(entry.source_size, Some(2));
+
+        // Content with same size and modification time is not read.
+        let source_path = config.source_directory_path.join("a.txt");
+        std::fs::write(&source_path, "A9").unwrap();
+        File::options()
+            .write(true)
+            .open(&source_path)
+            .unwrap()
+            .set_modified(entry.source_modified.unwrap())
+            .unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest2), CacheDiff::default());
+
+        config.always_hash_sources = true;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest2.diff(&cache_manifest3).changed,
+            vec!["a", "out"]
+        );
+    }
+
+    #[test]
+    fn test_save_cache_manifest_atomic() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        std::fs::write(&cache_manifest_path, "{\"V1\": {\"map\": {}, ").unwrap();
+        assert!(load_cache_manifest::<DummyError>(&cache_manifest_path).is_err());
+        std::fs::remove_file(&cache_manifest_path).unwrap();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        cache_manifest
+            .save::<DummyError>(&cache_manifest_path)
+            .unwrap();
+        assert_eq!(
+            load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap(),
+            cache_manifest
+        );
+
+        let temp_files: Vec<PathBuf> = std::fs::read_dir(temp_directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "tmp"))
+            .collect();
+        assert!(temp_files.is_empty());
+    }
+
+    #[test]
+    fn test_pack_lock() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        config.auto_prune = true;
+        let filter_registry = test_filter_registry();
+
+        let lock = PackLock::acquire::<DummyError>(&config).unwrap();
+        let err = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err.error_type, AssetErrorType::LockError(path) if path.starts_with(&config.internal_directory_path))
+        );
+        assert!(!cache_manifest_path.exists());
+        drop(lock);
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        // Lock file is not pruned.
+        assert!(config.internal_directory_path.join(".pack.lock").exists());
+    }
+
+    #[test]
+    fn test_clean() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(
+            clean::<DummyError>(&manifest_path, &cache_manifest_path, &config)
+                .unwrap()
+                .is_empty()
+        );
+
+        let stray_directory_path = config.internal_directory_path.join("old").join("nested");
+        create_dir_all(&stray_directory_path).unwrap();
+        std::fs::write(stray_directory_path.join("stray.txt"), "stray").unwrap();
+        let outside_directory_path = temp_directory.path().join("outside");
+        create_dir(&outside_directory_path).unwrap();
+        std::fs::write(outside_directory_path.join("keep.txt"), "keep").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(
+            &outside_directory_path,
+            config.target_directory_path.join("link"),
+        )
+        .unwrap();
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets = vec!["a".to_string(), "b".to_string()];
+        manifest.save(&manifest_path).unwrap();
+        let pruned = clean::<DummyError>(&manifest_path, &cache_manifest_path, &config).unwrap();
+
+        let out_path = cache_manifest1.get_entry("out").unwrap().path;
+        assert!(pruned.contains(&config.internal_directory_path.join(&out_path)));
+        assert!(pruned.contains(&stray_directory_path.join("stray.txt")));
+        assert!(!config.internal_directory_path.join("old").exists());
+        assert!(!config
+            .internal_directory_path
+            .join(out_path.parent().unwrap())
+            .exists());
+        assert!(outside_directory_path.join("keep.txt").exists());
+
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest2.get_entry("out").is_none());
+        assert!(cache_manifest2
+            .internal_full_path("a", &config)
+            .unwrap()
+            .exists());
+    }
+
+    #[test]
+    fn test_asset_resolver() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets = vec!["out".to_string()];
+        manifest.save(&manifest_path).unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+
+        let resolver = AssetResolver::load::<DummyError>(&cache_manifest_path).unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_path = cache_manifest.get_entry("out").unwrap().path;
+        assert_eq!(resolver.resolve("out"), Some(out_path.as_path()));
+        assert_eq!(resolver.resolve("a"), None);
+        assert_eq!(resolver.resolve("missing"), None);
+
+        let expected_url = format!(
+            "/static/out_text/{}",
+            out_path.file_name().unwrap().to_str().unwrap()
+        );
+        assert_eq!(
+            resolver.resolve_url("out", "/static/"),
+            Some(expected_url.clone())
+        );
+        assert_eq!(resolver.resolve_url("out", "/static"), Some(expected_url));
+        assert_eq!(resolver.resolve_url("a", "/static"), None);
+    }
+
+    #[test]
+    fn test_content_hash_naming_reproducible() {
+        let mut paths: Vec<Vec<PathBuf>> = Vec::new();
+        for _ in 0..2 {
+            let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+                prepare_test("assets_public.json");
+            config.naming = NamingStrategy::ContentHash;
+            let filter_registry = test_filter_registry();
+
+            pack(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+            let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+            // Forced rebuild with identical content keeps file names.
+            pack_with_should_rebuild(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+                &|_entry, _data| Some(true),
+            )
+            .unwrap();
+            let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+            let entry_paths = |cache_manifest: &AssetCacheManifest| -> Vec<PathBuf> {
+                ["a", "b", "out"]
+                    .iter()
+                    .map(|name| cache_manifest.get_entry(name).unwrap().path)
+                    .collect()
+            };
+            assert_eq!(entry_paths(&cache_manifest1), entry_paths(&cache_manifest2));
+            paths.push(entry_paths(&cache_manifest2));
+        }
+        assert_eq!(paths[0], paths[1]);
+    }
+
+    #[test]
+    fn test_filename_template() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        config.naming = NamingStrategy::ContentHash;
+        config.hash_format = HashFormat {
+            length: Some(8),
+            encoding: HashEncoding::Hex,
+        };
+        config.filename_template = "{name}.{hash}{ext}".to_string();
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let a_hash = crate::asset_cache::content_hash(
+            config.hash_algorithm,
+            &config.source_directory_path.join("a.txt"),
+        )
+        .unwrap();
+        let a_hash_hex = a_hash.to_hex();
+        assert_eq!(
+            cache_manifest.get_entry("a").unwrap().path,
+            PathBuf::from(format!("a.{}.txt", &a_hash_hex[..8]))
+        );
+        assert!(cache_manifest
+            .target_full_path("a", &config)
+            .unwrap()
+            .exists());
+
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        let data = manifest.assets.get("out").unwrap();
+        config.filename_template = "{hash}/{name}{ext}{unknown}".to_string();
+        assert_eq!(
+            data.output_path("out", "0", &config),
+            PathBuf::from("out_text/0/out.txt{unknown}")
+        );
+
+        // Existing cache entries keep their paths, so cache manifest is removed.
+        std::fs::remove_file(&cache_manifest_path).unwrap();
+        config.filename_template = "../{name}-{uuid}{ext}".to_string();
+        let err = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap_err();
+        assert!(matches!(err.error_type, AssetErrorType::AssetPathError(_)));
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let mut cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_internal_path = cache_manifest.internal_full_path("out", &config).unwrap();
+        let out_target_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert!(out_internal_path.exists() && out_target_path.exists());
+
+        assert!(cache_manifest
+            .remove_entry::<DummyError>("out", &config)
+            .unwrap());
+        assert!(cache_manifest.get_entry("out").is_none());
+        assert_eq!(cache_manifest.public_path("out"), None);
+        assert!(!out_internal_path.exists());
+        assert!(!out_target_path.exists());
+
+        // Inputs are kept.
+        for name in ["a", "b"] {
+            assert!(cache_manifest
+                .internal_full_path(name, &config)
+                .unwrap()
+                .exists());
+        }
+
+        assert!(!cache_manifest
+            .remove_entry::<DummyError>("out", &config)
+            .unwrap());
+        assert!(!cache_manifest
+            .remove_entry::<DummyError>("missing", &config)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_integrity() {
+        assert_eq!(
+            IntegrityAlgorithm::Sha256.integrity(&[b"a", b"bc"]),
+            "sha256-ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+        );
+
+        let (temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.get_entry("a").unwrap().integrity, None);
+
+        // Integrity is added to existing entries without rebuilding them.
+        config.integrity = Some(IntegrityAlgorithm::Sha384);
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        for name in ["a", "b", "out"] {
+            let entry = cache_manifest2.get_entry(name).unwrap();
+            assert_eq!(entry.path, cache_manifest1.get_entry(name).unwrap().path);
+            let target_bytes =
+                std::fs::read(cache_manifest2.target_full_path(name, &config).unwrap()).unwrap();
+            assert_eq!(
+                entry.integrity,
+                Some(IntegrityAlgorithm::Sha384.integrity(&[&target_bytes]))
+            );
+        }
+
+        let web_manifest_path = temp_directory.path().join("manifest.json");
+        write_web_manifest::<DummyError>(&cache_manifest2, &web_manifest_path).unwrap();
+        let web_manifest: HashMap<String, WebManifestEntry> =
+            serde_json::from_reader(File::open(&web_manifest_path).unwrap()).unwrap();
+        assert_eq!(web_manifest.len(), 3);
+        let out_entry = cache_manifest2.get_entry("out").unwrap();
+        assert_eq!(
+            web_manifest.get("out"),
+            Some(&WebManifestEntry {
+                path: out_entry.path,
+                integrity: out_entry.integrity,
+            })
+        );
+
+        // Rebuilt assets get integrity when they are built.
+        std::fs::write(config.source_directory_path.join("a.txt"), "A3").unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest3.get_entry("a").unwrap().integrity,
+            Some(IntegrityAlgorithm::Sha384.integrity(&[b"A3"]))
+        );
+    }
+
+    #[test]
+    fn test_source_directory_paths() {
+        let (temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let vendor_directory_path = temp_directory.path().join("vendor");
+        create_dir(&vendor_directory_path).unwrap();
+        std::fs::rename(
+            config.source_directory_path.join("b.txt"),
+            vendor_directory_path.join("b.txt"),
+        )
+        .unwrap();
+        std::fs::write(vendor_directory_path.join("a.txt"), "vendor").unwrap();
+
+        let err = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap_err();
+        match err.error_type {
+            AssetErrorType::AssetSourceNotFound(path) => assert_eq!(path, PathBuf::from("b.txt")),
+            error_type => panic!("{:?}", error_type),
+        }
+
+        config.source_directory_paths = vec![vendor_directory_path.clone()];
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        // First matching directory is used.
+        assert_eq!(
+            std::fs::read_to_string(cache_manifest.target_full_path("a", &config).unwrap())
+                .unwrap(),
+            "A1"
+        );
+        assert_eq!(
+            std::fs::read(cache_manifest.target_full_path("b", &config).unwrap()).unwrap(),
+            std::fs::read(vendor_directory_path.join("b.txt")).unwrap()
+        );
+
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        assert_eq!(
+            manifest.affected_public_assets(&vendor_directory_path.join("b.txt"), &config),
+            vec!["b", "out"]
+        );
+    }
+
+    #[test]
+    fn test_hash_algorithm() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        config.hash_algorithm = HashAlgorithm::Sha256;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let a_bytes = std::fs::read(config.source_directory_path.join("a.txt")).unwrap();
+        let a_hash = cache_manifest1.get_entry("a").unwrap().file_hash.unwrap();
+        assert_eq!(a_hash.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(
+            a_hash,
+            AssetHash::of_bytes(HashAlgorithm::Sha256, a_bytes.as_slice())
+        );
+        assert_ne!(
+            a_hash,
+            AssetHash::of_bytes(HashAlgorithm::Blake3, a_bytes.as_slice())
+        );
+
+        let a_hash_json = serde_json::to_value(&a_hash).unwrap();
+        assert!(a_hash_json.as_str().unwrap().starts_with("sha256:"));
+        assert_eq!(
+            serde_json::from_value::<AssetHash>(a_hash_json).unwrap(),
+            a_hash
+        );
+        assert!(serde_json::from_value::<AssetHash>(serde_json::json!("md5:AAAA")).is_err());
+
+        // Same algorithm, nothing is rebuilt.
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(cache_manifest1.diff(&cache_manifest2), CacheDiff::default());
+
+        // Stored hashes are replaced by hashes made by new algorithm.
+        config.hash_algorithm = HashAlgorithm::Blake3;
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest3 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest3.get_entry("a").unwrap().file_hash.unwrap(),
+            AssetHash::of_bytes(HashAlgorithm::Blake3, a_bytes.as_slice())
+        );
+    }
+
+    #[test]
+    fn test_publish_mode() {
+        for publish_mode in [PublishMode::Symlink, PublishMode::Hardlink] {
+            let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+                prepare_test("assets_public.json");
+            let filter_registry = test_filter_registry();
+            config.publish_mode = publish_mode;
+
+            pack(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+            let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+            let internal_full_path = cache_manifest.internal_full_path("a", &config).unwrap();
+            let target_full_path = cache_manifest.target_full_path("a", &config).unwrap();
+            assert_eq!(
+                std::fs::read(&target_full_path).unwrap(),
+                std::fs::read(&internal_full_path).unwrap()
+            );
+            let is_symlink = target_full_path
+                .symlink_metadata()
+                .unwrap()
+                .file_type()
+                .is_symlink();
+            assert_eq!(is_symlink, publish_mode == PublishMode::Symlink);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                assert_eq!(
+                    std::fs::metadata(&target_full_path).unwrap().ino(),
+                    std::fs::metadata(&internal_full_path).unwrap().ino()
+                );
+            }
+
+            // Migrated links point to renamed internal file.
+            let mut cache_manifest = cache_manifest;
+            cache_manifest
+                .migrate_naming::<DummyError>(&config, NamingStrategy::ContentHash)
+                .unwrap();
+            crate::save_cache_manifest::<DummyError>(&cache_manifest_path, &cache_manifest)
+                .unwrap();
+            config.naming = NamingStrategy::ContentHash;
+            assert!(target_full_path.symlink_metadata().is_err());
+            let target_full_path = cache_manifest.target_full_path("a", &config).unwrap();
+            assert_eq!(
+                std::fs::read(&target_full_path).unwrap(),
+                std::fs::read(cache_manifest.internal_full_path("a", &config).unwrap()).unwrap()
+            );
+
+            // Republishing as copy replaces link and keeps internal file.
+            let old_target_full_path = target_full_path;
+            config.publish_mode = PublishMode::Copy;
+            std::fs::write(config.source_directory_path.join("a.txt"), "A2").unwrap();
+            config.always_hash_sources = true;
+            pack(
+                &manifest_path,
+                &cache_manifest_path,
+                &config,
+                &filter_registry,
+            )
+            .unwrap();
+            let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+            // Old target file (link) of rebuilt asset is removed.
+            assert!(old_target_full_path.symlink_metadata().is_err());
+            let target_full_path = cache_manifest.target_full_path("a", &config).unwrap();
+            assert!(!target_full_path
+                .symlink_metadata()
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(std::fs::read(&target_full_path).unwrap(), b"A2");
+            assert_eq!(
+                std::fs::read(cache_manifest.internal_full_path("a", &config).unwrap()).unwrap(),
+                b"A2"
+            );
+        }
+    }
+
+    #[test]
+    fn test_precompress() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.precompress = vec![Compression::Gzip, Compression::Brotli];
+        config.always_hash_sources = true;
+
+        let read_variants = |config: &AssetConfig| {
+            let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+            let mut full_path = cache_manifest
+                .target_full_path("a", config)
+                .unwrap()
+                .into_os_string();
+            full_path.push(".gz");
+            let gz_full_path = PathBuf::from(full_path);
+            let br_full_path = gz_full_path.with_extension("br");
+            (cache_manifest, gz_full_path, br_full_path)
+        };
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let (cache_manifest, gz_full_path, br_full_path) = read_variants(&config);
+        assert_eq!(
+            cache_manifest.get_entry("a").unwrap().precompressed,
+            vec![Compression::Gzip, Compression::Brotli]
+        );
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(&gz_full_path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "A1");
+        let mut content = String::new();
+        brotli::Decompressor::new(File::open(&br_full_path).unwrap(), 4096)
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "A1");
+        let runtime_manifest = RuntimeManifest::build(&cache_manifest, &config).unwrap();
+        assert_eq!(runtime_manifest.assets["a"].encodings.len(), 2);
+
+        // Variants of unchanged asset are not written again.
+        std::fs::write(&gz_full_path, "GZ").unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&gz_full_path).unwrap(), b"GZ");
+
+        // Variants of compressions removed from config are removed.
+        config.precompress = vec![Compression::Gzip];
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let (cache_manifest, _, _) = read_variants(&config);
+        assert_eq!(
+            cache_manifest.get_entry("a").unwrap().precompressed,
+            vec![Compression::Gzip]
+        );
+        assert!(!br_full_path.exists());
+
+        std::fs::write(config.source_directory_path.join("a.txt"), "A2").unwrap();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let (_, gz_full_path, _) = read_variants(&config);
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(&gz_full_path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "A2");
+    }
+
+    #[test]
+    fn test_preserve_metadata() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.preserve_metadata = true;
+
+        let source_path = config.source_directory_path.join("a.txt");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&source_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&source_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        for full_path in [
+            cache_manifest.internal_full_path("a", &config).unwrap(),
+            cache_manifest.target_full_path("a", &config).unwrap(),
+        ] {
+            let metadata = std::fs::metadata(&full_path).unwrap();
+            assert_eq!(metadata.modified().unwrap(), modified);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+            }
+        }
+
+        // Filtered output is not changed.
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert_ne!(
+            std::fs::metadata(out_full_path)
+                .unwrap()
+                .modified()
+                .unwrap(),
+            modified
+        );
+    }
+
+    #[test]
+    fn test_pack_report_rebuilt_and_skipped() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(report.rebuilt, vec!["a", "b", "out"]);
+        assert!(report.skipped.is_empty());
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let total_size: u64 = ["a", "b", "out"]
+            .iter()
+            .map(|name| {
+                std::fs::metadata(cache_manifest.target_full_path(name, &config).unwrap())
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+        // Internal and target files are written.
+        assert_eq!(report.bytes_written, 2 * total_size);
+
+        let report = pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert!(report.rebuilt.is_empty());
+        assert_eq!(report.skipped, vec!["a", "b", "out"]);
+        assert_eq!(report.bytes_written, 0);
+    }
+
+    #[test]
+    fn test_pack_dry_run() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.always_hash_sources = true;
+
+        let plan = pack_dry_run(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(plan.names(), vec!["a", "b", "out"]);
+        assert!(!cache_manifest_path.exists());
+        assert_eq!(
+            std::fs::read_dir(&config.internal_directory_path)
+                .unwrap()
+                .count(),
+            0
+        );
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest_bytes = std::fs::read(&cache_manifest_path).unwrap();
+
+        std::fs::write(config.source_directory_path.join("a.txt"), "A2").unwrap();
+        let plan = pack_dry_run(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        assert_eq!(plan.names(), vec!["a", "out"]);
+        assert!(!plan.rebuilds[0].tentative);
+        assert!(plan.rebuilds[1].tentative);
+        assert_eq!(
+            std::fs::read(&cache_manifest_path).unwrap(),
+            cache_manifest_bytes
+        );
+    }
+
+    #[test]
+    fn test_pack_single() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+
+        // Not public asset is built with its inputs, but not copied to target directory.
+        let report = pack_single(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            "mid",
+        )
+        .unwrap();
+        assert!(report.rebuilt.contains(&"mid".to_string()));
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest.get_entry("out").is_none());
+        assert!(cache_manifest
+            .internal_full_path("mid", &config)
+            .unwrap()
+            .exists());
+        assert!(!cache_manifest
+            .target_full_path("mid", &config)
+            .unwrap()
+            .exists());
+
+        let report = pack_single(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            "out",
+        )
+        .unwrap();
+        assert_eq!(report.rebuilt, vec!["out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest
+            .target_full_path("out", &config)
+            .unwrap()
+            .exists());
+
+        let result = pack_single(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            "missing",
+        );
+        match result.unwrap_err().error_type {
+            AssetErrorType::AssetNotFoundInManifestError(name) => assert_eq!(name, "missing"),
+            error_type => panic!("{:?}", error_type),
+        }
+    }
+
+    #[test]
+    fn test_manifest_from_str() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let manifest_string = std::fs::read_to_string(&manifest_path).unwrap();
+        let manifest = AssetManifest::from_str::<DummyError>(&manifest_string).unwrap();
+        assert_eq!(
+            AssetManifest::from_reader::<_, DummyError>(manifest_string.as_bytes()).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            load_manifest::<DummyError>(&manifest_path).unwrap(),
+            manifest
+        );
+        match AssetManifest::from_str::<DummyError>("{")
+            .unwrap_err()
+            .error_type
+        {
+            AssetErrorType::JSONError(_) => {}
+            error_type => panic!("{:?}", error_type),
+        }
+
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["a", "b", "out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest
+            .target_full_path("out", &config)
+            .unwrap()
+            .exists());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_manifest() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let toml_manifest_path = temp_directory.path().join("assets.toml");
+        std::fs::write(
+            &toml_manifest_path,
+            r#"
+public_assets = ["a", "b", "out"]
+
+[assets.a]
+extension = "txt"
+source = { File = "a.txt" }
+
+[assets.b]
+extension = "txt"
+source = { File = "b.txt" }
+
+[assets.out]
+output_base_path = "out_text"
+extension = "txt"
+
+[assets.out.source.Filtered]
+filter_name = "TestCat"
+input_names = ["a", "b"]
+options = { additional_text = { String = "test" } }
+"#,
+        )
+        .unwrap();
+
+        let manifest = load_manifest::<DummyError>(&toml_manifest_path).unwrap();
+        assert_eq!(
+            manifest,
+            load_manifest::<DummyError>(&manifest_path).unwrap()
+        );
+
+        // Externally tagged sources are written as tables and read back.
+        let manifest_string = toml::to_string(&manifest).unwrap();
+        assert_eq!(
+            AssetManifest::from_toml_str::<DummyError>(&manifest_string).unwrap(),
+            manifest
+        );
+
+        match AssetManifest::from_toml_str::<DummyError>("[assets.a]\nsource = { Unknown = 1 }")
+            .unwrap_err()
+            .error_type
+        {
+            AssetErrorType::ManifestParseError { asset, .. } => {
+                assert_eq!(asset, Some("a".to_string()))
+            }
+            error_type => panic!("{:?}", error_type),
+        }
+
+        pack(
+            &toml_manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest
+            .target_full_path("out", &config)
+            .unwrap()
+            .exists());
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn test_watch() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.always_hash_sources = true;
+
+        let reports: std::cell::RefCell<Vec<Vec<String>>> = std::cell::RefCell::new(Vec::new());
+        crate::watch::watch_while(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|result| {
+                let mut reports = reports.borrow_mut();
+                reports.push(result.as_ref().unwrap().rebuilt.clone());
+                if reports.len() == 1 {
+                    std::fs::write(config.source_directory_path.join("a.txt"), "A2").unwrap();
+                }
+                reports.len() < 2
+            },
+        )
+        .unwrap();
+
+        let reports = reports.into_inner();
+        assert_eq!(reports[0], vec!["a", "b", "out"]);
+        assert_eq!(reports[1], vec!["a", "out"]);
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn test_watch_manifest_changed() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.always_hash_sources = true;
+
+        // Changed manifest is loaded again on next run, though it is not in watched directory.
+        let reports: std::cell::RefCell<Vec<Vec<String>>> = std::cell::RefCell::new(Vec::new());
+        crate::watch::watch_while(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|result| {
+                let mut reports = reports.borrow_mut();
+                reports.push(result.as_ref().unwrap().rebuilt.clone());
+                if reports.len() == 1 {
+                    let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+                    std::fs::write(&manifest_path, manifest.replace("\"test\"", "\"test2\""))
+                        .unwrap();
+                    File::options()
+                        .write(true)
+                        .open(&manifest_path)
+                        .unwrap()
+                        .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+                        .unwrap();
+                    std::fs::write(config.source_directory_path.join("b.txt"), "B2").unwrap();
+                }
+                reports.len() < 2
+            },
+        )
+        .unwrap();
+
+        let reports = reports.into_inner();
+        assert_eq!(reports[0], vec!["a", "b", "out"]);
+        assert_eq!(reports[1], vec!["b", "out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert!(std::fs::read_to_string(out_full_path)
+            .unwrap()
+            .ends_with("B2test2"));
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn test_watch_ignores_output_paths() {
+        let (_temp_directory, mut config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.always_hash_sources = true;
+        config.internal_directory_path = config.source_directory_path.join("internal");
+        config.target_directory_path = config.source_directory_path.join("target");
+        let cache_manifest_path = config.source_directory_path.join("cache.json");
+
+        // Files written by pack runs do not trigger next run, only changed sources do.
+        let reports: std::cell::RefCell<Vec<crate::asset_pack::PackReport>> =
+            std::cell::RefCell::new(Vec::new());
+        crate::watch::watch_while(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &|result| {
+                let mut reports = reports.borrow_mut();
+                reports.push(result.as_ref().unwrap().clone());
+                if reports.len() == 1 {
+                    // Source is changed after events of first run are debounced.
+                    let source_path = config.source_directory_path.join("a.txt");
+                    std::thread::spawn(move || {
+                        std::thread::sleep(crate::watch::DEBOUNCE_DURATION * 3);
+                        std::fs::write(source_path, "A2").unwrap();
+                    });
+                }
+                reports.len() < 2
+            },
+        )
+        .unwrap();
+
+        let reports = reports.into_inner();
+        assert_eq!(reports[0].rebuilt, vec!["a", "b", "out"]);
+        assert_eq!(reports[1].rebuilt, vec!["a", "out"]);
+    }
+
+    #[test]
+    fn test_pack_with_observer() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        /// Observer counting built assets only, other events are ignored by default.
+        #[derive(Default)]
+        struct ProgressObserver {
+            built: std::sync::Mutex<Vec<(String, u64)>>,
+        }
+
+        impl PackObserver for ProgressObserver {
+            fn on_asset_built(&self, name: &str, bytes: u64) {
+                self.built.lock().unwrap().push((name.to_string(), bytes));
+            }
+        }
+
+        let observer = ProgressObserver::default();
+        pack_with_observer(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+            &observer,
+        )
+        .unwrap();
+        let built = observer.built.into_inner().unwrap();
+        assert_eq!(
+            built
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "out"]
+        );
+        assert_eq!(built[0].1, 2);
+
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        let mut cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let report = cache_manifest
+            .process_public_assets_with_observer(
+                &config,
+                &manifest,
+                &filter_registry,
+                &NoopObserver,
+            )
+            .unwrap();
+        assert_eq!(report.skipped, vec!["a", "b", "out"]);
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let (_temp_directory, config, _manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let manifest_template = r#"{
+            "assets": {
+                "a": {"source": {"File": "a.txt"}, "extension": "txt"},
+                "x": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["a", "y"], "options": {}}}, "extension": "txt"},
+                "y": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["x"], "options": {}}}, "extension": "txt"},
+                "r": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["p"], "options": {}}}, "extension": "txt"},
+                "p": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["q"], "options": {}}}, "extension": "txt"},
+                "q": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["a", "r"], "options": {}}}, "extension": "txt"}
+            },
+            "public_assets": [PUBLIC]
+        }"#;
+        for (public_asset, expected_cycle) in [("y", vec!["x", "y"]), ("r", vec!["p", "q", "r"])] {
+            let manifest = AssetManifest::from_str::<DummyError>(
+                &manifest_template.replace("PUBLIC", &format!("{:?}", public_asset)),
+            )
+            .unwrap();
+
+            match AssetCacheManifest::default()
+                .plan(&config, &manifest, &filter_registry)
+                .unwrap_err()
+                .error_type
+            {
+                AssetErrorType::DependencyCycle(cycle) => assert_eq!(cycle, expected_cycle),
+                error_type => panic!("unexpected error {:?}", error_type),
+            }
+
+            let result = pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry);
+            match result.unwrap_err().error_type {
+                AssetErrorType::DependencyCycle(cycle) => assert_eq!(cycle, expected_cycle),
+                error_type => panic!("unexpected error {:?}", error_type),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_dependency_graph() {
+        const DEPTH: usize = 20_000;
+        let filter_registry = test_filter_registry();
+
+        let chain_manifest = |first_input: Option<String>| {
+            let mut assets = serde_json::Map::new();
+            assets.insert(
+                "asset0".to_string(),
+                match &first_input {
+                    Some(input_name) => serde_json::json!({
+                        "source": {"Filtered": {"filter_name": "TestCat", "input_names": [input_name], "options": {}}},
+                        "extension": "txt"
+                    }),
+                    None => serde_json::json!({"source": {"Inline": ""}, "extension": "txt"}),
+                },
+            );
+            for index in 1..DEPTH {
+                assets.insert(
+                    format!("asset{}", index),
+                    serde_json::json!({
+                        "source": {"Filtered": {"filter_name": "TestCat", "input_names": [format!("asset{}", index - 1)], "options": {}}},
+                        "extension": "txt"
+                    }),
+                );
+            }
+            serde_json::from_value::<AssetManifest>(serde_json::json!({
+                "assets": assets,
+                "public_assets": [format!("asset{}", DEPTH - 1)]
+            }))
+            .unwrap()
+        };
+
+        let manifest = chain_manifest(None);
+        let graph = DependencyGraph::new(&manifest);
+        let order = graph.topological_order().unwrap();
+        assert_eq!(order.len(), DEPTH);
+        assert_eq!(order[0], "asset0");
+        assert_eq!(order[DEPTH - 1], format!("asset{}", DEPTH - 1));
+        validate_manifest(&manifest, &filter_registry).unwrap();
+
+        let manifest = chain_manifest(Some(format!("asset{}", DEPTH - 1)));
+        let cycle = DependencyGraph::new(&manifest).find_cycle().unwrap();
+        assert_eq!(cycle.len(), DEPTH);
+        assert_eq!(cycle[0], "asset0");
+        assert_eq!(cycle[1], format!("asset{}", DEPTH - 1));
+        assert!(validate_manifest(&manifest, &filter_registry).is_err());
+    }
+
+    #[test]
+    fn test_validate_manifest() {
+        let (_temp_directory, _config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        validate_manifest(&manifest, &filter_registry).unwrap();
+
+        let manifest = AssetManifest::from_str::<DummyError>(
+            r#"{
+                "assets": {
+                    "a": {"source": {"File": "a.txt"}, "extension": "txt", "hash_format": {"length": 8, "encoding": "Uuid"}},
+                    "b2": {"source": {"File": "b.txt"}, "extension": "txt", "hash_format": {"length": 0, "encoding": "Hex"}},
+                    "b3": {"source": {"File": "b.txt"}, "extension": "txt", "hash_format": {"length": 8, "encoding": "Hex"}},
+                    "x": {"source": {"Filtered": {"filter_name": "Missing", "input_names": ["a", "y"], "options": {}}}, "extension": "txt"},
+                    "y": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["x", "b"], "options": {}}}, "extension": "txt"}
+                },
+                "public_assets": ["y", "c"]
+            }"#,
+        )
+        .unwrap();
+        let errors: Vec<String> = validate_manifest(&manifest, &filter_registry)
+            .unwrap_err()
+            .iter()
+            .map(|error_type| error_type.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "public asset c is not defined in manifest",
+                "hash format of asset a has zero length or length with UUID encoding",
+                "hash format of asset b2 has zero length or length with UUID encoding",
+                "asset b is not found in manifest",
+                "filter Missing is not found",
+                "dependency cycle of assets x -> y",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_manifests() {
+        let (temp_directory, _config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+
+        let mut first = manifest.clone();
+        first.assets.remove("out");
+        first.public_assets = vec!["a".to_string(), "b".to_string()];
+        let mut second = manifest.clone();
+        second.assets.remove("b");
+        second.public_assets = vec!["b".to_string(), "out".to_string()];
+        let first_path = temp_directory.path().join("first.json");
+        let second_path = temp_directory.path().join("second.json");
+        first.save(&first_path).unwrap();
+        second.save(&second_path).unwrap();
+
+        let merged = load_manifests::<DummyError>(&[first_path, second_path]).unwrap();
+        assert_eq!(merged, manifest);
+
+        let mut merged_again = merged.clone();
+        merged_again.merge::<DummyError>(manifest.clone()).unwrap();
+        assert_eq!(merged_again, manifest);
+
+        let mut conflicting = manifest.clone();
+        conflicting.assets.get_mut("a").unwrap().extension = "css".to_string();
+        conflicting.public_assets = vec!["c".to_string()];
+        match merged_again.merge::<DummyError>(conflicting) {
+            Err(AssetErrorType::ConflictingAssetError(name)) => assert_eq!(name, "a"),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(merged_again, manifest);
+    }
+
+    #[test]
+    fn test_inline_source() {
+        let (_temp_directory, mut config, _manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.naming = NamingStrategy::ContentHash;
+
+        let manifest_template = r#"{
+            "assets": {
+                "version": {"source": {"Inline": "CONTENT"}, "extension": "txt"},
+                "a": {"source": {"File": "a.txt"}, "extension": "txt"},
+                "out": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["version", "a"], "options": {}}}, "extension": "txt"}
+            },
+            "public_assets": ["version", "out"]
+        }"#;
+        let manifest_with_content = |content: &str| {
+            AssetManifest::from_str::<DummyError>(
+                &manifest_template.replace("CONTENT", &BASE64_STANDARD.encode(content)),
+            )
+            .unwrap()
+        };
+
+        let manifest = manifest_with_content("1.0.0");
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["version", "a", "out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let version_path = cache_manifest.target_full_path("version", &config).unwrap();
+        assert_eq!(std::fs::read_to_string(&version_path).unwrap(), "1.0.0");
+        assert_eq!(
+            config.target_directory_path.join(
+                manifest
+                    .predict_output_path::<DummyError>("version", &config)
+                    .unwrap()
+            ),
+            version_path
+        );
+        assert!(
+            std::fs::read_to_string(cache_manifest.target_full_path("out", &config).unwrap())
+                .unwrap()
+                .starts_with("1.0.0")
+        );
+
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert!(report.rebuilt.is_empty());
+
+        let manifest = manifest_with_content("1.0.1");
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["version", "out"]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(cache_manifest.target_full_path("version", &config).unwrap())
+                .unwrap(),
+            "1.0.1"
+        );
+    }
+
+    #[test]
+    fn test_directory_source() {
+        let (_temp_directory, mut config, _manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.always_hash_sources = true;
+
+        let fonts_path = config.source_directory_path.join("fonts");
+        create_dir_all(fonts_path.join("extra")).unwrap();
+        std::fs::write(fonts_path.join("b.txt"), "B").unwrap();
+        std::fs::write(fonts_path.join("extra").join("a.txt"), "A").unwrap();
+
+        let manifest = AssetManifest::from_str::<DummyError>(
+            r#"{
+                "assets": {
+                    "fonts": {"source": {"Directory": "fonts"}, "extension": ""},
+                    "out": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["fonts"], "options": {}}}, "extension": "txt"}
+                },
+                "public_assets": ["fonts", "out"]
+            }"#,
+        )
+        .unwrap();
+
+        pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let fonts_entry = cache_manifest.get_entry("fonts").unwrap();
+        assert_eq!(
+            fonts_entry.files,
+            vec![PathBuf::from("b.txt"), PathBuf::from("extra/a.txt")]
+        );
+        let target_fonts_path = cache_manifest.target_full_path("fonts", &config).unwrap();
+        assert!(target_fonts_path.is_dir());
+        assert_eq!(
+            std::fs::read_to_string(target_fonts_path.join("extra").join("a.txt")).unwrap(),
+            "A"
+        );
+        assert!(
+            std::fs::read_to_string(cache_manifest.target_full_path("out", &config).unwrap())
+                .unwrap()
+                .starts_with("BA")
+        );
+
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert!(report.rebuilt.is_empty());
+        assert_eq!(
+            manifest.assets_with_source_file(Path::new("fonts/extra/a.txt")),
+            vec!["fonts"]
+        );
+
+        std::fs::write(fonts_path.join("extra").join("a.txt"), "C").unwrap();
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["fonts", "out"]);
+        let mut cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let new_target_fonts_path = cache_manifest.target_full_path("fonts", &config).unwrap();
+        assert!(!target_fonts_path.exists());
+        assert_eq!(
+            std::fs::read_to_string(new_target_fonts_path.join("extra").join("a.txt")).unwrap(),
+            "C"
+        );
+
+        let pruned = cache_manifest.prune(&config, &manifest).unwrap();
+        assert!(pruned.is_empty(), "{:?}", pruned);
+    }
+
+    #[test]
+    fn test_directory_source_existing_content_path() {
+        let (_temp_directory, mut config, _manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        config.naming = NamingStrategy::ContentHash;
+
+        let fonts_path = config.source_directory_path.join("fonts");
+        create_dir_all(&fonts_path).unwrap();
+        std::fs::write(fonts_path.join("b.txt"), "B").unwrap();
+        let manifest = AssetManifest::from_str::<DummyError>(
+            r#"{
+                "assets": {
+                    "fonts": {"source": {"Directory": "fonts"}, "extension": ""}
+                },
+                "public_assets": ["fonts"]
+            }"#,
+        )
+        .unwrap();
+
+        pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let internal_fonts_path = cache_manifest1
+            .internal_full_path("fonts", &config)
+            .unwrap();
+        assert!(internal_fonts_path.is_dir());
+
+        // Cache manifest is lost, identical directory with content hash name is reused.
+        std::fs::remove_file(&cache_manifest_path).unwrap();
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["fonts"]);
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            cache_manifest2.get_entry("fonts").unwrap().path,
+            cache_manifest1.get_entry("fonts").unwrap().path
+        );
+        assert_eq!(
+            std::fs::read_to_string(internal_fonts_path.join("b.txt")).unwrap(),
+            "B"
+        );
+
+        // Directory with same name and different content is collision.
+        std::fs::write(internal_fonts_path.join("b.txt"), "X").unwrap();
+        std::fs::remove_file(&cache_manifest_path).unwrap();
+        let result = pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry);
+        assert!(matches!(
+            result.unwrap_err().error_type,
+            AssetErrorType::OutputHashCollision { .. }
+        ));
+    }
+
+    #[test]
+    fn test_public_asset_not_defined() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        manifest.public_assets.push("typo".to_string());
+        let result = pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry);
+        match result.unwrap_err().error_type {
+            AssetErrorType::PublicAssetNotDefined(name) => assert_eq!(name, "typo"),
+            error_type => panic!("unexpected error {:?}", error_type),
+        }
+        // Nothing is built before error.
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest.get_entry("a").is_none());
+    }
+
+    #[test]
+    fn test_write_public_manifest() {
+        let (temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let filter_registry = test_filter_registry();
+
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let public_manifest_path = temp_directory.path().join("manifest.json");
+        cache_manifest
+            .write_public_manifest(&public_manifest_path, &config)
+            .unwrap();
+
+        let public_manifest_string = std::fs::read_to_string(&public_manifest_path).unwrap();
+        let public_manifest: HashMap<String, PathBuf> =
+            serde_json::from_str(&public_manifest_string).unwrap();
+        assert_eq!(public_manifest.len(), 1);
+        assert_eq!(
+            config.target_directory_path.join(&public_manifest["out"]),
+            cache_manifest.target_full_path("out", &config).unwrap()
+        );
+        assert!(public_manifest_string.contains("\n  \"out\": "));
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        let (_temp_directory, mut config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        std::env::set_var("ARTUSHAK_TEST_CDN_HOST", "cdn.example.com");
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        if let AssetSource::Filtered(filtered) = &mut manifest.assets.get_mut("out").unwrap().source
+        {
+            filtered.options.insert(
+                "additional_text".to_string(),
+                AssetFilterOption::String("|${ARTUSHAK_TEST_CDN_HOST}/${".to_string()),
+            );
+        }
+
+        pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert!(std::fs::read_to_string(out_full_path)
+            .unwrap()
+            .ends_with("|${ARTUSHAK_TEST_CDN_HOST}/${"));
+
+        config.expand_env_vars = true;
+        config.always_hash_sources = true;
+        std::fs::write(config.source_directory_path.join("a.txt"), "A2").unwrap();
+        pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert!(std::fs::read_to_string(out_full_path)
+            .unwrap()
+            .ends_with("|cdn.example.com/${"));
+
+        // Changed variable value changes expanded options, so asset is rebuilt without source changes.
+        std::env::set_var("ARTUSHAK_TEST_CDN_HOST", "cdn2.example.com");
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.rebuilt, vec!["out".to_string()]);
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let out_full_path = cache_manifest.target_full_path("out", &config).unwrap();
+        assert!(std::fs::read_to_string(out_full_path)
+            .unwrap()
+            .ends_with("|cdn2.example.com/${"));
+
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert!(report.rebuilt.is_empty());
+
+        let mut options = HashMap::new();
+        options.insert(
+            "list".to_string(),
+            AssetFilterOption::StringList(vec!["${ARTUSHAK_TEST_UNDEFINED_VAR}".to_string()]),
+        );
+        match expand_env_vars::<DummyError>(&options)
+            .unwrap_err()
+            .error_type
+        {
+            AssetErrorType::UndefinedEnvVar(name) => {
+                assert_eq!(name, "ARTUSHAK_TEST_UNDEFINED_VAR")
+            }
+            error_type => panic!("unexpected error {:?}", error_type),
+        }
+    }
+
+    #[test]
+    fn test_copy_filter() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = AssetFilterRegistry::<DummyError>::new(HashMap::new())
+            .with_filter(CopyFilter::NAME, Box::new(CopyFilter {}));
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        if let AssetSource::Filtered(filtered) = &mut manifest.assets.get_mut("out").unwrap().source
+        {
+            filtered.filter_name = CopyFilter::NAME.to_string();
+        }
+        manifest.assets.get_mut("out").unwrap().output_base_path =
+            Some(PathBuf::from("nested/out_text"));
+
+        pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(cache_manifest.target_full_path("out", &config).unwrap())
+                .unwrap(),
+            "A1B"
+        );
+    }
+
+    #[test]
+    fn test_concat_filter() {
+        let temp_directory = TempDir::new().unwrap();
+        let input_paths: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let input_path = temp_directory.path().join(name);
+                std::fs::write(&input_path, name.to_uppercase()).unwrap();
+                input_path
+            })
+            .collect();
+        let output_path = temp_directory.path().join("out").join("concat.txt");
+        let filter = ConcatFilter {};
+
+        let mut options = HashMap::new();
+        AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "ABC");
+
+        options.insert(
+            "separator".to_string(),
+            AssetFilterOption::String(";\n".to_string()),
+        );
+        AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "A;\nB;\nC");
+
+        AssetFilter::<DummyError>::process_asset_file(&filter, &[], &output_path, &options)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "");
+        assert!(AssetFilter::<DummyError>::accepts_empty_inputs(&filter));
+
+        options.insert("separator".to_string(), AssetFilterOption::Integer(1));
+        let result = AssetFilter::<DummyError>::process_asset_file(
+            &filter,
+            &input_paths,
+            &output_path,
+            &options,
+        );
+        assert!(matches!(
+            result.unwrap_err().error_type,
+            AssetErrorType::InvalidFilterOption { .. }
+        ));
+    }
+
+    #[test]
+    fn test_remove_undefined_assets() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert!(report.removed.is_empty());
+        let cache_manifest1 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+
+        manifest.assets.remove("out");
+        manifest.public_assets = vec!["a".to_string()];
+        let report =
+            pack_manifest(&manifest, &cache_manifest_path, &config, &filter_registry).unwrap();
+        assert_eq!(report.removed, vec!["out"]);
+        let cache_manifest2 = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        assert!(cache_manifest2.get_entry("out").is_none());
+        assert!(cache_manifest2.public_path("out").is_none());
+        assert!(!cache_manifest1
+            .target_full_path("out", &config)
+            .unwrap()
+            .exists());
+        assert!(!cache_manifest1
+            .internal_full_path("out", &config)
+            .unwrap()
+            .exists());
+        // Asset still defined in manifest is kept, though it is not public anymore.
+        assert!(cache_manifest2
+            .internal_full_path("b", &config)
+            .unwrap()
+            .exists());
+    }
+
+    #[test]
+    fn test_unreachable_assets() {
+        let (_temp_directory, _config, manifest_path, _cache_manifest_path) =
+            prepare_test("assets_chain.json");
+        let mut manifest = load_manifest::<DummyError>(&manifest_path).unwrap();
+        assert!(manifest.unreachable_assets().is_empty());
+
+        manifest.public_assets = vec!["mid".to_string()];
+        let mut dead = manifest.assets["a"].clone();
+        dead.source = AssetSource::File(PathBuf::from("dead.txt"));
+        manifest.assets.insert("dead".to_string(), dead);
+        assert_eq!(manifest.unreachable_assets(), vec!["dead", "out"]);
+    }
+
+    #[test]
+    fn test_shared_input_checked_once_per_run() {
+        let (_temp_directory, config, _manifest_path, _cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        let manifest = AssetManifest::from_str::<DummyError>(
+            r#"{
+                "assets": {
+                    "a": {"source": {"File": "a.txt"}, "extension": "txt"},
+                    "shared": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["a"], "options": {}}}, "extension": "txt"},
+                    "x": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["shared"], "options": {}}}, "extension": "txt"},
+                    "y": {"source": {"Filtered": {"filter_name": "TestCat", "input_names": ["shared", "x"], "options": {}}}, "extension": "txt"}
+                },
+                "public_assets": ["x", "y"]
+            }"#,
+        )
+        .unwrap();
+        let mut cache_manifest = AssetCacheManifest::default();
+
+        let checked: Arc<std::sync::Mutex<Vec<String>>> = Arc::default();
+        let checked_by_callback = checked.clone();
+        let should_rebuild = move |cache_entry: &AssetCacheEntry, _: &AssetData| {
+            checked_by_callback
+                .lock()
+                .unwrap()
+                .push(cache_entry.name.clone());
+            None
+        };
+        for run in 0..2 {
+            let mut context = PackContext::new(&config, &manifest, &filter_registry);
+            context.should_rebuild = Some(&should_rebuild);
+            cache_manifest
+                .process_public_asset_names(&manifest.public_assets, &|_| true, &mut context)
+                .unwrap();
+            assert_eq!(
+                context.report.resolution_order,
+                vec!["a", "shared", "x", "y"]
+            );
+            if run == 1 {
+                assert!(context.report.rebuilt.is_empty());
+            }
+        }
+        // Cached entries are checked only in second run, once each.
+        let mut checked = checked.lock().unwrap().clone();
+        checked.sort();
+        assert_eq!(checked, vec!["a", "shared", "x", "y"]);
+    }
+
+    #[test]
+    fn test_content_hash_large_file() {
+        let temp_directory = TempDir::new().unwrap();
+        let file_path = temp_directory.path().join("large.bin");
+        // Several megabytes of non-repeating data, so chunked and parallel hashing are exercised.
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let bytes: Vec<u8> = (0..5 * 1024 * 1024 + 17)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        std::fs::write(&file_path, &bytes).unwrap();
+
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            assert_eq!(
+                crate::asset_cache::content_hash(algorithm, &file_path).unwrap(),
+                AssetHash::of_bytes(algorithm, &bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn test_integrity_of_reader() {
+        let bytes: Vec<u8> = (0..100_000u32).map(|value| (value % 251) as u8).collect();
+        for algorithm in [
+            IntegrityAlgorithm::Sha256,
+            IntegrityAlgorithm::Sha384,
+            IntegrityAlgorithm::Sha512,
+        ] {
+            assert_eq!(
+                algorithm
+                    .integrity_of_reader(b"/*! banner */\n".chain(bytes.as_slice()))
+                    .unwrap(),
+                algorithm.integrity(&[b"/*! banner */\n", &bytes])
+            );
+        }
+    }
+    #[test]
+    fn test_asset_hash_hex_encoding() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let hash = AssetHash::of_bytes(algorithm, b"test");
+            let prefix = match algorithm {
+                HashAlgorithm::Blake3 => String::new(),
+                algorithm => format!("{}:", algorithm.name()),
+            };
+
+            let hex = format!("{}{}", prefix, hash.to_hex());
+            let base64 = format!("{}{}", prefix, BASE64_STANDARD.encode(&hash.hash));
+            assert_eq!(
+                serde_json::from_value::<AssetHash>(serde_json::json!(hex)).unwrap(),
+                hash
+            );
+            assert_eq!(
+                serde_json::from_value::<AssetHash>(serde_json::json!(format!(
+                    "{}{}",
+                    prefix,
+                    hash.to_hex().to_uppercase()
+                )))
+                .unwrap(),
+                hash
+            );
+            assert_eq!(
+                serde_json::from_value::<AssetHash>(serde_json::json!(base64)).unwrap(),
+                hash
+            );
+
+            let serialized = serde_json::to_value(&hash).unwrap();
+            #[cfg(feature = "hex-hash")]
+            assert_eq!(serialized, serde_json::json!(hex));
+            #[cfg(not(feature = "hex-hash"))]
+            assert_eq!(serialized, serde_json::json!(base64));
+            assert_eq!(
+                serde_json::from_value::<AssetHash>(serialized).unwrap(),
+                hash
+            );
+
+            // Hexadecimal string of right length with non-hexadecimal digit.
+            let malformed = format!("{}{}", prefix, "g".repeat(algorithm.output_len() * 2));
+            assert!(serde_json::from_value::<AssetHash>(serde_json::json!(malformed)).is_err());
+            // Valid hexadecimal string of wrong length.
+            let short = format!("{}{}", prefix, &hash.to_hex()[2..]);
+            assert!(serde_json::from_value::<AssetHash>(serde_json::json!(short)).is_err());
+        }
+    }
+    #[test]
+    fn test_asset_hash_display_from_str() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let hash = AssetHash::of_bytes(algorithm, b"test");
+            let formatted = hash.to_string();
+            assert_eq!(
+                serde_json::to_value(&hash).unwrap(),
+                serde_json::json!(formatted)
+            );
+            assert_eq!(formatted.parse::<AssetHash>().unwrap(), hash);
+        }
+
+        assert_eq!(
+            "md5:AAAA".parse::<AssetHash>().unwrap_err(),
+            ParseAssetHashError::UnknownAlgorithm("md5".to_string())
+        );
+        assert_eq!(
+            "not base64!".parse::<AssetHash>().unwrap_err(),
+            ParseAssetHashError::InvalidEncoding("not base64!".to_string())
+        );
+        assert_eq!(
+            "sha256:AAAA".parse::<AssetHash>().unwrap_err(),
+            ParseAssetHashError::InvalidLength {
+                expected: 32,
+                actual: 3
+            }
+        );
+        assert_eq!(
+            "AAAA".parse::<AssetHash>().unwrap_err().to_string(),
+            "hash has length 3 bytes, expected 32 bytes"
+        );
+    }
+    #[test]
+    fn test_hash_file() {
+        let (_temp_directory, config, manifest_path, cache_manifest_path) =
+            prepare_test("assets_public.json");
+        let filter_registry = test_filter_registry();
+        pack(
+            &manifest_path,
+            &cache_manifest_path,
+            &config,
+            &filter_registry,
+        )
+        .unwrap();
+
+        let cache_manifest = load_cache_manifest::<DummyError>(&cache_manifest_path).unwrap();
+        let a_path = config.source_directory_path.join("a.txt");
+        let a_hash = cache_manifest.get_entry("a").unwrap().file_hash.unwrap();
+        assert_eq!(hash_file(&a_path).unwrap(), a_hash);
+        assert_eq!(hash_bytes(&std::fs::read(&a_path).unwrap()), a_hash);
+        assert_eq!(
+            a_hash.hash,
+            *blake3::hash(&std::fs::read(&a_path).unwrap()).as_bytes()
+        );
+        assert!(hash_file(&config.source_directory_path.join("missing.txt")).is_err());
+    }
+    #[test]
+    fn test_import_bundle_rejects_links() {
+        let temp_directory = TempDir::new().unwrap();
+        let outside_directory_path = temp_directory.path().join("outside");
+        std::fs::create_dir_all(&outside_directory_path).unwrap();
+
+        let bundle_path = temp_directory.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(File::create(&bundle_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder
+            .append_link(&mut header, "target/x", &outside_directory_path)
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "target/x/file", &b"evil"[..])
+            .unwrap();
+        builder.into_inner().unwrap();
+
+        let import_directory_path = temp_directory.path().join("import");
+        let result =
+            AssetCacheManifest::import_bundle::<DummyError>(&bundle_path, &import_directory_path);
+        match result.unwrap_err().error_type {
+            AssetErrorType::AssetPathError(path) => assert_eq!(path, Path::new("target/x")),
+            _ => panic!("Wrong error type"),
+        }
+        assert!(!outside_directory_path.join("file").exists());
+        assert!(!import_directory_path.join("x").exists());
+    }
 }
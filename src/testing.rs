@@ -0,0 +1,59 @@
+use std::{
+    collections::HashMap,
+    fs::{self, create_dir},
+    path::PathBuf,
+};
+
+use tempfile::TempDir;
+
+use crate::{
+    asset_filter::{AssetFilter, AssetFilterOption, FilterContext},
+    assets::{AssetFilterError, AssetResult},
+};
+
+/// Run filter on given input contents in temporary directory and return output content. Output is checked with `validate_output` like during pack run.
+pub fn run_filter<F, E>(
+    filter: &F,
+    inputs: &[&[u8]],
+    options: &HashMap<String, AssetFilterOption>,
+) -> AssetResult<Vec<u8>, E>
+where
+    F: AssetFilter<E> + ?Sized,
+    E: AssetFilterError,
+{
+    run_filter_with_context(filter, inputs, options, &FilterContext::default())
+}
+
+/// Same as `run_filter`, but pass given filter context to filter.
+pub fn run_filter_with_context<F, E>(
+    filter: &F,
+    inputs: &[&[u8]],
+    options: &HashMap<String, AssetFilterOption>,
+    context: &FilterContext,
+) -> AssetResult<Vec<u8>, E>
+where
+    F: AssetFilter<E> + ?Sized,
+    E: AssetFilterError,
+{
+    let temp_directory = TempDir::new()?;
+    let input_directory_path = temp_directory.path().join("input");
+    create_dir(&input_directory_path)?;
+
+    let mut input_file_paths: Vec<PathBuf> = Vec::with_capacity(inputs.len());
+    for (index, input) in inputs.iter().enumerate() {
+        let input_file_path = input_directory_path.join(format!("input-{}", index));
+        fs::write(&input_file_path, input)?;
+        input_file_paths.push(input_file_path);
+    }
+    let output_file_path = temp_directory.path().join("output");
+
+    filter.process_asset_file_with_context(
+        &input_file_paths,
+        &output_file_path,
+        options,
+        context,
+    )?;
+    filter.validate_output(&output_file_path, options)?;
+
+    Ok(fs::read(&output_file_path)?)
+}
@@ -0,0 +1,264 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, SystemTime},
+};
+
+use log::{debug, error, info, warn};
+use notify::{
+    event::{AccessKind, AccessMode},
+    Event, EventKind, RecursiveMode, Watcher,
+};
+
+use crate::{
+    asset_config::AssetConfig,
+    asset_filter::AssetFilterRegistry,
+    asset_pack::PackReport,
+    assets::{AssetFilterError, AssetManifest, AssetResult},
+    dependency_graph::DependencyGraph,
+    load_manifest, pack_changed_in_graph, pack_manifest,
+};
+
+/// Time to wait for more filesystem events after event is received, so burst of events (for example, editor saving file) causes single rebuild.
+pub const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+/// Pack assets (see `pack`), then watch source directories and pack assets again after every change of source files. After change only public assets with changed source files in their dependencies are processed (see `pack_changed`), all assets are processed if manifest file is changed. Manifest and its dependency graph are loaded again only if modification time of manifest file is changed. Cache manifest is saved after every run.
+///
+/// Changes of files in internal directory, target directories and of cache manifest file are ignored, so pack runs do not trigger each other if these paths are inside source directories.
+///
+/// Errors of pack runs are logged and do not stop watching. Function returns only if source directories can not be watched.
+pub fn watch<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    watch_while(
+        manifest_path,
+        cache_manifest_path,
+        config,
+        filter_registry,
+        &|_| true,
+    )
+}
+
+/// Same as `watch`, but callback is called with result of every pack run, and watching is stopped when it returns `false`.
+pub fn watch_while<E>(
+    manifest_path: &Path,
+    cache_manifest_path: &Path,
+    config: &AssetConfig,
+    filter_registry: &AssetFilterRegistry<E>,
+    keep_watching: &dyn Fn(&AssetResult<PackReport, E>) -> bool,
+) -> AssetResult<(), E>
+where
+    E: AssetFilterError,
+{
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // Receiver is dropped only after watching is stopped.
+        let _ = sender.send(event);
+    })
+    .map_err(notify_error)?;
+    for source_directory_path in
+        std::iter::once(&config.source_directory_path).chain(&config.source_directory_paths)
+    {
+        debug!("Watching {:?}", source_directory_path);
+        watcher
+            .watch(source_directory_path, RecursiveMode::Recursive)
+            .map_err(notify_error)?;
+    }
+
+    let mut loaded_manifest: Option<LoadedManifest> = None;
+    let mut changed_paths: Option<Vec<PathBuf>> = None;
+    loop {
+        let manifest_modified = modification_time(manifest_path);
+        let result = match (&loaded_manifest, &changed_paths) {
+            (Some(loaded_manifest), Some(changed_paths))
+                if loaded_manifest.modified == manifest_modified
+                    && !contains_path(changed_paths, manifest_path) =>
+            {
+                pack_changed_in_graph(
+                    changed_paths,
+                    &loaded_manifest.manifest,
+                    &loaded_manifest.graph,
+                    cache_manifest_path,
+                    config,
+                    filter_registry,
+                )
+            }
+            _ => {
+                loaded_manifest = None;
+                load_manifest(manifest_path).and_then(|manifest| {
+                    debug!("Building dependency graph of manifest");
+                    let loaded_manifest = loaded_manifest.insert(LoadedManifest {
+                        modified: manifest_modified,
+                        graph: DependencyGraph::new(&manifest),
+                        manifest,
+                    });
+                    pack_manifest(
+                        &loaded_manifest.manifest,
+                        cache_manifest_path,
+                        config,
+                        filter_registry,
+                    )
+                })
+            }
+        };
+        match &result {
+            Ok(report) => {
+                for name in &report.rebuilt {
+                    info!("Rebuilt asset {}", name);
+                }
+            }
+            Err(err) => error!("Failed to pack assets: {}", err),
+        }
+        if !keep_watching(&result) {
+            return Ok(());
+        }
+
+        let ignored_paths = ignored_paths(cache_manifest_path, config);
+        changed_paths = Some(wait_for_changes(
+            &receiver,
+            &ignored_paths,
+            cache_manifest_path,
+        )?);
+    }
+}
+
+/// Manifest loaded by `watch_while` with its dependency graph.
+struct LoadedManifest {
+    /// Modification time of manifest file when it was loaded.
+    modified: Option<SystemTime>,
+    manifest: AssetManifest,
+    graph: DependencyGraph,
+}
+
+/// Return modification time of file, `None` if it can not be read.
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Block until relevant filesystem event is received, then wait until no events are received for `DEBOUNCE_DURATION`. Return paths of all relevant events.
+fn wait_for_changes(
+    receiver: &Receiver<notify::Result<Event>>,
+    ignored_paths: &[PathBuf],
+    cache_manifest_path: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let mut changed_paths: Vec<PathBuf> = Vec::new();
+    while changed_paths.is_empty() {
+        let event = receiver
+            .recv()
+            .map_err(|_| io::Error::other("filesystem watcher stopped"))?;
+        add_relevant_paths(
+            event,
+            ignored_paths,
+            cache_manifest_path,
+            &mut changed_paths,
+        );
+    }
+    loop {
+        match receiver.recv_timeout(DEBOUNCE_DURATION) {
+            Ok(event) => add_relevant_paths(
+                event,
+                ignored_paths,
+                cache_manifest_path,
+                &mut changed_paths,
+            ),
+            Err(RecvTimeoutError::Timeout) => {
+                changed_paths.sort();
+                changed_paths.dedup();
+                return Ok(changed_paths);
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("filesystem watcher stopped"))
+            }
+        }
+    }
+}
+
+/// Add paths of event to changed_paths if event can mean change of source files. Read access is ignored, as pack run reads source files itself, paths under ignored_paths and temporary files of cache manifest are ignored as pack run writes them.
+fn add_relevant_paths(
+    event: notify::Result<Event>,
+    ignored_paths: &[PathBuf],
+    cache_manifest_path: &Path,
+    changed_paths: &mut Vec<PathBuf>,
+) {
+    match event {
+        Ok(event) => {
+            debug!("Filesystem event {:?} for {:?}", event.kind, event.paths);
+            match event.kind {
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => {}
+                EventKind::Access(_) => return,
+                _ => {}
+            }
+            changed_paths.extend(event.paths.into_iter().filter(|path| {
+                !ignored_paths
+                    .iter()
+                    .any(|ignored_path| path.starts_with(ignored_path))
+                    && !is_cache_manifest_temp_file(path, cache_manifest_path)
+            }));
+        }
+        Err(err) => warn!("Filesystem watcher error: {}", err),
+    }
+}
+
+/// Return paths written by pack runs: internal directory, target directories and cache manifest file, both as given and canonicalized (if they exist).
+fn ignored_paths(cache_manifest_path: &Path, config: &AssetConfig) -> Vec<PathBuf> {
+    let mut ignored_paths: Vec<PathBuf> = Vec::new();
+    for path in [
+        cache_manifest_path,
+        &config.internal_directory_path,
+        &config.target_directory_path,
+    ]
+    .iter()
+    .copied()
+    .chain(
+        config
+            .targets
+            .iter()
+            .map(|target| target.directory_path.as_path()),
+    ) {
+        if let Ok(canonical_path) = std::fs::canonicalize(path) {
+            ignored_paths.push(canonical_path);
+        }
+        ignored_paths.push(path.to_path_buf());
+    }
+    ignored_paths
+}
+
+/// Return `true` if file name of path is name of temporary file written by `AssetCacheManifestV1::save` for given cache manifest.
+fn is_cache_manifest_temp_file(path: &Path, cache_manifest_path: &Path) -> bool {
+    match (path.file_name(), cache_manifest_path.file_name()) {
+        (Some(file_name), Some(cache_manifest_file_name)) => {
+            let file_name = file_name.to_string_lossy();
+            file_name
+                .strip_prefix('.')
+                .and_then(|file_name| {
+                    file_name.strip_prefix(cache_manifest_file_name.to_string_lossy().as_ref())
+                })
+                .is_some_and(|suffix| suffix.starts_with('.') && suffix.ends_with(".tmp"))
+        }
+        _ => false,
+    }
+}
+
+/// Return `true` if paths contain given path (compared canonicalized if possible).
+fn contains_path(paths: &[PathBuf], path: &Path) -> bool {
+    let canonical_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    paths
+        .iter()
+        .any(|changed_path| changed_path == path || *changed_path == canonical_path)
+}
+
+fn notify_error(err: notify::Error) -> io::Error {
+    match err.kind {
+        notify::ErrorKind::Io(err) => err,
+        _ => io::Error::other(err.to_string()),
+    }
+}